@@ -1,9 +1,18 @@
 use crate::inverter::Lri::{
     BatAmp, BatChaStt, BatTmpVal, BatVol,
-    DcMsAmp, DcMsVol,
+    BatChrgBatChrg, BatDschBatDsch, BatChrgDyBatChrg, BatDschDyBatDsch, BatDiagChrgW, BatDiagDschW, BatOpStt,
+    BatDiagCapacThrpCnt, BatDiagTotAhIn, BatDiagTotAhOut, BatDiagActlCapacNom, BatCapacRtgWh,
+    DcMsAmp, DcMsVol, DcMsWatt,
     AcMsVol0, AcMsVol1, AcMsVol2,
     AcMsAmp0, AcMsAmp1, AcMsAmp2,
-    MeteringDyWhOut, MeteringTotWhOut,
+    AcMsWatt0, AcMsWatt1, AcMsWatt2, GridMsTotW,
+    AcMsVAr0, AcMsVAr1, AcMsVAr2,
+    AcMsVA0, AcMsVA1, AcMsVA2,
+    GridMsHz, GridMsTotPF,
+    OperationGriSwStt, OperationHealth, CoolsysTmpVal,
+    NameplateLocation, NameplateMainModel, NameplateModel, NameplatePkgRev,
+    MeteringDyWhOut, MeteringTotWhOut, MeteringTotFeedTms, MeteringTotOpTms,
+    MeteringGridMsTotWOut, MeteringGridMsTotWIn, MeteringGridMsTotWhOut, MeteringGridMsTotWhIn,
 };
 
 use crate::log;
@@ -12,20 +21,114 @@ use bytebuffer_new::ByteBuffer;
 use bytebuffer_new::Endian::{BigEndian, LittleEndian};
 use socket2::{SockAddr, Socket};
 use std::borrow::BorrowMut;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
 use std::mem::MaybeUninit;
 use std::net::SocketAddr;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 #[derive(Clone)]
 pub struct Inverter {
     pub address: SocketAddr,
+    /// SUSy id and serial of the device, known after login.
+    pub device_susy_id: u16,
+    pub device_serial: u32,
     packet_id: u32,
     susy_id: u16,
     serial: u32,
+    /// Kept to log in again when the session got lost.
+    credentials: Option<(UserGroup, String)>,
+    /// Requests that failed in a row, see `needs_login`.
+    failures: u32,
+    session_expired: bool,
+    login_backoff: Duration,
+    next_login: Instant,
 }
 
-pub struct InverterError {
-    pub message: &'static str,
+#[derive(Debug)]
+pub enum InverterError {
+    Io(io::Error),
+    Timeout,
+    BadMagic,
+    WrongPacketId,
+    WrongSource,
+    LoginRejected,
+    /// The device does not know the requested LRI range (SMA error code 21).
+    Unsupported(u32),
+    DeviceErrorCode(u16),
+    Truncated,
+    /// Passwords are limited to the 12 byte field of the login packet.
+    PasswordTooLong,
+}
+
+impl fmt::Display for InverterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InverterError::Io(error) => write!(f, "I/O error: {}", error),
+            InverterError::Timeout => write!(f, "Timeout waiting for response"),
+            InverterError::BadMagic => write!(f, "Wrong magic number"),
+            InverterError::WrongPacketId => write!(f, "Wrong packet id"),
+            InverterError::WrongSource => write!(f, "Wrong source address"),
+            InverterError::LoginRejected => write!(f, "Login failed"),
+            InverterError::Unsupported(lri) => write!(f, "Unsupported LRI 0x{:08X}", lri),
+            InverterError::DeviceErrorCode(code) => write!(f, "Error code {}", code),
+            InverterError::Truncated => write!(f, "Truncated packet"),
+            InverterError::PasswordTooLong => {
+                write!(f, "Password longer than {} bytes", Inverter::PASSWORD_LENGTH)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InverterError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            InverterError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for InverterError {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => InverterError::Timeout,
+            _ => InverterError::Io(error),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum UserGroup {
+    User,
+    Installer,
+}
+
+impl UserGroup {
+    fn id(self) -> u32 {
+        match self {
+            UserGroup::User => 0x07,
+            UserGroup::Installer => 0x0A,
+        }
+    }
+
+    /// The password is obfuscated by adding a group specific byte to every character.
+    fn enc_char(self) -> u8 {
+        match self {
+            UserGroup::User => 0x88,
+            UserGroup::Installer => 0xBB,
+        }
+    }
+}
+
+/// Header fields of a response to the current request, `buffer` is positioned after the header.
+struct Response {
+    buffer: ByteBuffer,
+    source_susy_id: u16,
+    source_serial: u32,
+    error_code: u16,
+    fragment_id: u16,
 }
 
 pub struct DataType {
@@ -34,6 +137,18 @@ pub struct DataType {
     last: u32,
 }
 
+impl DataType {
+    /// Counter records hold a single 64-bit value, status and nameplate records eight words and
+    /// all other records five words.
+    fn record_size(&self) -> usize {
+        match self.command {
+            0x54000200 => 16,
+            0x51800200 | 0x58000200 => 40,
+            _ => 28,
+        }
+    }
+}
+
 const fn gen_susy_id() -> u16 {
     125
 }
@@ -43,11 +158,28 @@ fn gen_serial() -> u32 {
 }
 
 pub enum Lri {
+    OperationHealth = 0x00214800,   // *08* Condition (aka INV_STATUS)
+    OperationGriSwStt = 0x00416400, // *08* Grid relay/contactor (aka INV_GRIDRELAY)
+
+    NameplateLocation = 0x00821E00,  // *10* Device name (aka INV_NAME)
+    NameplateMainModel = 0x00821F00, // *08* Device class (aka INV_CLASS)
+    NameplateModel = 0x00822000,     // *08* Device type (aka INV_TYPE)
+    NameplatePkgRev = 0x00823400,    // *08* Software package (aka INV_SWVER)
+
+    CoolsysTmpVal = 0x00237700,    // *40* Internal inverter temperature (aka INV_TEMP)
+
     BatChaStt = 0x00295A00,        // *00* Current battery charge status
 
+    DcMsWatt = 0x00251E00,         // *40* DC power input (aka SPOT_PDC1 / SPOT_PDC2)
     DcMsVol = 0x00451F00,          // *40* DC voltage input (aka SPOT_UDC1 / SPOT_UDC2)
     DcMsAmp = 0x00452100,          // *40* DC current input (aka SPOT_IDC1 / SPOT_IDC2)
 
+    GridMsTotW = 0x00263F00,       // *40* AC power total (aka SPOT_PACTOT)
+
+    AcMsWatt0 = 0x00464000,        // *40* AC power phase 1 (aka SPOT_PAC1)
+    AcMsWatt1 = 0x00464100,        // *40* AC power phase 2 (aka SPOT_PAC2)
+    AcMsWatt2 = 0x00464200,        // *40* AC power phase 3 (aka SPOT_PAC3)
+
     AcMsVol0 = 0x00464800,         // *40* AC voltage input (aka SPOT_UAC1)
     AcMsVol1 = 0x00464900,         // *40* AC voltage input (aka SPOT_UAC2)
     AcMsVol2 = 0x00464A00,         // *40* AC voltage input (aka SPOT_UAC3)
@@ -56,42 +188,417 @@ pub enum Lri {
     AcMsAmp1 = 0x00465400,         // *40* AC current input (aka SPOT_IAC2)
     AcMsAmp2 = 0x00465500,         // *40* AC current input (aka SPOT_IAC3)
 
+    GridMsHz = 0x00465700,         // *00* Grid frequency (aka SPOT_FREQ)
+
+    GridMsTotPF = 0x00665900,      // *00* Displacement power factor
+
+    AcMsVAr0 = 0x00666000,         // *40* AC reactive power phase 1
+    AcMsVAr1 = 0x00666100,         // *40* AC reactive power phase 2
+    AcMsVAr2 = 0x00666200,         // *40* AC reactive power phase 3
+
+    AcMsVA0 = 0x00666800,          // *40* AC apparent power phase 1
+    AcMsVA1 = 0x00666900,          // *40* AC apparent power phase 2
+    AcMsVA2 = 0x00666A00,          // *40* AC apparent power phase 3
+
+    BatDiagCapacThrpCnt = 0x00491E00, // *40* Number of battery charge throughputs (aka BAT_CYCLES)
+    BatDiagTotAhIn = 0x00492600,   // *00* Amp hours counter for battery charge
+    BatDiagTotAhOut = 0x00492700,  // *00* Amp hours counter for battery discharge
+    BatDiagActlCapacNom = 0x00492900, // *00* Current battery capacity in percent of nominal (state of health)
+    BatCapacRtgWh = 0x00492A00,    // *00* Rated battery capacity
     BatTmpVal = 0x00495B00,        // *40* Battery temperature
     BatVol = 0x00495C00,           // *40* Battery voltage
     BatAmp = 0x00495D00,           // *40* Battery current
+    BatOpStt = 0x00495E00,         // *08* Battery operating status
+
+    BatChrgBatChrg = 0x00496700,   // *00* Total energy charged into the battery
+    BatDschBatDsch = 0x00496800,   // *00* Total energy discharged from the battery
+    BatDiagChrgW = 0x00496900,     // *40* Battery charging power
+    BatDiagDschW = 0x00496A00,     // *40* Battery discharging power
+    BatChrgDyBatChrg = 0x00496B00, // *00* Energy charged into the battery today
+    BatDschDyBatDsch = 0x00496C00, // *00* Energy discharged from the battery today
 
     MeteringTotWhOut = 0x00260100, // *00* Total yield (aka SPOT_ETOTAL)
     MeteringDyWhOut = 0x00262200,  // *00* Day yield (aka SPOT_ETODAY)
+    MeteringTotOpTms = 0x00462E00, // *00* Operating time (aka SPOT_OPERTM)
+    MeteringTotFeedTms = 0x00462F00, // *00* Feed-in time (aka SPOT_FEEDTM)
+    MeteringGridMsTotWhOut = 0x00462400, // *00* Total grid feed-in
+    MeteringGridMsTotWhIn = 0x00462500,  // *00* Total grid consumption
+    MeteringGridMsTotWOut = 0x00463600,  // *40* Grid feed-in power
+    MeteringGridMsTotWIn = 0x00463700,   // *40* Grid consumption power
+}
+
+/// `device_class` and `model` are status tags, e.g. 8001 for solar inverters.
+pub struct DeviceInfo {
+    pub name: String,
+    pub device_class: Option<u32>,
+    pub model: Option<u32>,
+    pub firmware: String,
 }
 
+/// Status tags as reported in *08* records.
+pub enum Tag {
+    Fault = 35,
+    Closed = 51,
+    Off = 303,
+    Ok = 307,
+    Open = 311,
+    Warning = 455,
+    Standby = 2291,
+    Charge = 2292,
+    Discharge = 2293,
+    SolarInverters = 8001,
+    BatteryInverters = 8007,
+    ElectricityMeter = 8065,
+    CommunicationProducts = 8128,
+}
+
+pub enum DeviceClass {
+    SolarInverter,
+    BatteryInverter,
+    EnergyMeter,
+    HomeManager,
+    CommunicationProduct,
+    /// Any other class tag, `None` if the device did not report its class.
+    Other(Option<u32>),
+}
+
+impl DeviceClass {
+    /// Energy meters and the Home Manager are recognised by their SUSy id.
+    pub fn new(susy_id: u16, device_class: Option<u32>) -> Self {
+        match (susy_id, device_class) {
+            (270 | 349, _) => DeviceClass::EnergyMeter,
+            (372, _) => DeviceClass::HomeManager,
+            (_, Some(tag)) if tag == Tag::SolarInverters as u32 => DeviceClass::SolarInverter,
+            (_, Some(tag)) if tag == Tag::BatteryInverters as u32 => DeviceClass::BatteryInverter,
+            (_, Some(tag)) if tag == Tag::ElectricityMeter as u32 => DeviceClass::EnergyMeter,
+            (_, Some(tag)) if tag == Tag::CommunicationProducts as u32 => DeviceClass::CommunicationProduct,
+            (_, device_class) => DeviceClass::Other(device_class),
+        }
+    }
+}
+
+impl DeviceClass {
+    /// Only devices known not to be inverters are skipped, e.g. hybrid inverters report a class
+    /// of their own.
+    pub fn is_inverter(&self) -> bool {
+        !matches!(
+            self,
+            DeviceClass::EnergyMeter | DeviceClass::HomeManager | DeviceClass::CommunicationProduct
+        )
+    }
+}
+
+impl fmt::Display for DeviceClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceClass::SolarInverter => write!(f, "solar inverter"),
+            DeviceClass::BatteryInverter => write!(f, "battery inverter"),
+            DeviceClass::EnergyMeter => write!(f, "energy meter"),
+            DeviceClass::HomeManager => write!(f, "Home Manager"),
+            DeviceClass::CommunicationProduct => write!(f, "communication product"),
+            DeviceClass::Other(Some(tag)) => write!(f, "device class {}", tag),
+            DeviceClass::Other(None) => write!(f, "unknown device class"),
+        }
+    }
+}
+
+/// A device that answered discovery. SUSy id and serial are 0 if it did not answer the device
+/// info query.
+pub struct DiscoveredDevice {
+    pub address: SocketAddr,
+    pub susy_id: u16,
+    pub serial: u32,
+    pub device_class: DeviceClass,
+}
+
+impl DiscoveredDevice {
+    pub fn is_inverter(&self) -> bool {
+        self.device_class.is_inverter()
+    }
+}
+
+/// Currently selected status tags, `None` if the inverter did not report them.
+pub struct StatusInfo {
+    pub device_status: Option<u32>,
+    pub grid_relay: Option<u32>,
+}
+
+/// Values are keyed by the channel byte of the record (battery pack).
+/// Throughout the result structs `None` marks a value the inverter reported as not available.
 pub struct BatteryInfo {
-    pub temperature: [u16; 3],
-    pub voltage: [u16; 3],
-    pub current: [i16; 3],
+    pub temperature: BTreeMap<u8, Option<Measurement>>,
+    pub voltage: BTreeMap<u8, Option<Measurement>>,
+    pub current: BTreeMap<u8, Option<Measurement>>,
+    pub state_of_health: BTreeMap<u8, Option<Measurement>>,
+    pub rated_capacity: BTreeMap<u8, Option<Measurement>>,
+    pub charge_cycles: BTreeMap<u8, Option<u32>>,
+    pub charged_ah: BTreeMap<u8, Option<u32>>,
+    pub discharged_ah: BTreeMap<u8, Option<u32>>,
 }
 
+/// Values are keyed by the channel byte of the record (MPP tracker).
 pub struct DCInfo {
-    pub voltage: [u16; 2],
-    pub current: [u16; 2],
+    pub voltage: BTreeMap<u8, Option<Measurement>>,
+    pub current: BTreeMap<u8, Option<Measurement>>,
+    pub power: BTreeMap<u8, Option<Measurement>>,
 }
 
+/// Values are keyed by phase, starting at 1.
 pub struct ACInfo {
-    pub voltage: [u16; 3],
-    pub current: [u16; 3],
+    pub voltage: BTreeMap<u8, Option<Measurement>>,
+    pub current: BTreeMap<u8, Option<Measurement>>,
+}
+
+/// Values are keyed by phase, starting at 1.
+pub struct ACPowerInfo {
+    pub power: BTreeMap<u8, Option<Measurement>>,
+    pub total: Option<Measurement>,
+}
+
+/// Values are keyed by phase, starting at 1.
+pub struct GridInfo {
+    pub frequency: Option<Measurement>,
+    pub reactive_power: BTreeMap<u8, Option<Measurement>>,
+    pub apparent_power: BTreeMap<u8, Option<Measurement>>,
+    pub power_factor: Option<Measurement>,
+}
+
+/// Times are in seconds.
+pub struct OperationTimeInfo {
+    pub operating_time: Option<u64>,
+    pub feed_in_time: Option<u64>,
+}
+
+/// Values are keyed by the channel byte of the record (battery pack).
+pub struct BatteryEnergyInfo {
+    pub charged_wh: BTreeMap<u8, Option<u64>>,
+    pub discharged_wh: BTreeMap<u8, Option<u64>>,
+    pub daily_charged_wh: BTreeMap<u8, Option<u64>>,
+    pub daily_discharged_wh: BTreeMap<u8, Option<u64>>,
+}
+
+/// Values are keyed by the channel byte of the record (battery pack).
+pub struct BatteryStateInfo {
+    pub operating_state: BTreeMap<u8, u32>,
+    pub charge_power: BTreeMap<u8, Option<Measurement>>,
+    pub discharge_power: BTreeMap<u8, Option<Measurement>>,
+}
+
+/// Energy is in Wh, as measured at the grid connection point.
+pub struct GridExchangeInfo {
+    pub export_power: Option<Measurement>,
+    pub import_power: Option<Measurement>,
+    pub export_wh: Option<u64>,
+    pub import_wh: Option<u64>,
 }
 
 pub struct EnergyProductionInfo {
-    pub daily_wh: u32,
-    pub total_wh: u32,
+    pub daily_wh: Option<u64>,
+    pub total_wh: Option<u64>,
+}
+
+/// A measured value as reported by the inverter. Dividing `raw` by `divisor` gives the value in
+/// the base unit, e.g. voltages are reported in V/100 and currents in A/1000.
+#[derive(Clone, Copy)]
+pub struct Measurement {
+    pub raw: i64,
+    pub divisor: u32,
+}
+
+impl Measurement {
+    pub fn value(&self) -> f64 {
+        self.raw as f64 / self.divisor as f64
+    }
+
+    /// The value in thousandths of the base unit, e.g. mV.
+    pub fn milli(&self) -> f64 {
+        self.raw as f64 * 1000_f64 / self.divisor as f64
+    }
+}
+
+const NAN_S32: u32 = 0x80000000;
+const NAN_U32: u32 = 0xFFFFFFFF;
+const NAN_S64: u64 = 0x8000000000000000;
+const NAN_U64: u64 = 0xFFFFFFFFFFFFFFFF;
+
+const DT_STATUS: u8 = 0x08;
+const DT_STRING: u8 = 0x10;
+const DT_SLONG: u8 = 0x40;
+
+/// Value of a record, decoded according to the record size and the data type in its code.
+/// Numeric values are `None` if the inverter reports them as not available, e.g. at night.
+pub enum RecordValue {
+    U32(Option<u32>),
+    I32(Option<i32>),
+    U64(Option<u64>),
+    Status(Option<u32>),
+    String(String),
+}
+
+impl RecordValue {
+    /// Data types are not always reliable for 32-bit values, so the numeric accessors convert.
+    pub fn as_u32(&self) -> Option<u32> {
+        match self {
+            RecordValue::U32(value) => *value,
+            RecordValue::I32(value) => value.map(|value| value as u32),
+            RecordValue::U64(value) => value.map(|value| value as u32),
+            RecordValue::Status(tag) => *tag,
+            RecordValue::String(_) => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            RecordValue::U64(value) => *value,
+            _ => self.as_u32().map(|value| value as u64),
+        }
+    }
+
+    /// Signed records keep their sign, all other numeric records are unsigned.
+    pub fn as_measurement(&self, divisor: u32) -> Option<Measurement> {
+        let raw = match self {
+            RecordValue::I32(value) => value.map(i64::from),
+            RecordValue::U32(value) | RecordValue::Status(value) => value.map(i64::from),
+            RecordValue::U64(value) => value.map(|value| value as i64),
+            RecordValue::String(_) => None,
+        };
+        raw.map(|raw| Measurement { raw, divisor })
+    }
+
+    pub fn as_tag(&self) -> Option<u32> {
+        match self {
+            RecordValue::Status(tag) => *tag,
+            _ => None,
+        }
+    }
+}
+
+/// A single record of a response. `date` is the unix time of the measurement.
+pub struct Record {
+    pub code: u32,
+    pub lri: u32,
+    pub channel: u8,
+    pub data_type: u8,
+    pub date: u32,
+    pub value: RecordValue,
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:08x} (type {:02x}) at {}", self.code, self.data_type, self.date)
+    }
+}
+
+/// Iterates over the records of a response as returned from `get_data`.
+pub struct Records {
+    buffer: ByteBuffer,
+    record_size: usize,
+}
+
+impl Records {
+    /// Status records hold eight tag words. Each tag word holds the tag in the lower three bytes
+    /// and a selected flag in the upper byte, 0x00FFFFFE ends the list.
+    fn selected_tag(data: &[u8]) -> Option<u32> {
+        for attribute in data.chunks_exact(4) {
+            let tag = u32::from_le_bytes([attribute[0], attribute[1], attribute[2], 0]);
+            if tag == 0x00FFFFFE {
+                break;
+            }
+            if attribute[3] == 1 {
+                return Some(tag);
+            }
+        }
+        None
+    }
+
+    /// String records hold a NUL padded string of up to 32 bytes.
+    fn read_string(data: &[u8]) -> String {
+        let end = data.iter().position(|byte| *byte == 0).unwrap_or(data.len());
+        String::from_utf8_lossy(&data[0..end]).trim().to_string()
+    }
+
+    fn read_u32(data: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+    }
+
+    fn read_value_u32(data: &[u8], offset: usize) -> Option<u32> {
+        match Records::read_u32(data, offset) {
+            NAN_U32 => None,
+            value => Some(value),
+        }
+    }
+
+    /// Only 0x80000000 is not available for signed values, 0xFFFFFFFF is -1.
+    fn read_value_i32(data: &[u8], offset: usize) -> Option<i32> {
+        match Records::read_u32(data, offset) {
+            NAN_S32 => None,
+            value => Some(value as i32),
+        }
+    }
+
+    fn read_value_u64(data: &[u8]) -> Option<u64> {
+        match Records::read_u32(data, 0) as u64 | (Records::read_u32(data, 4) as u64) << 32 {
+            NAN_S64 | NAN_U64 => None,
+            value => Some(value),
+        }
+    }
+}
+
+impl Iterator for Records {
+    type Item = Record;
+
+    fn next(&mut self) -> Option<Record> {
+        if self.buffer.len() < self.buffer.get_rpos() + self.record_size {
+            return None;
+        }
+        let code = self.buffer.read_u32();
+        if code == 0 {
+            return None;
+        }
+        let date = self.buffer.read_u32();
+        let data = self.buffer.read_bytes(self.record_size - 8);
+        let data_type = (code >> 24) as u8;
+
+        let value = if self.record_size == 16 {
+            RecordValue::U64(Records::read_value_u64(&data))
+        } else if data_type == DT_STATUS {
+            RecordValue::Status(Records::selected_tag(&data))
+        } else if data_type == DT_STRING {
+            RecordValue::String(Records::read_string(&data))
+        } else if self.record_size == 40 {
+            // Long numeric records, e.g. the software package, keep the value in the fifth word.
+            RecordValue::U32(Records::read_value_u32(&data, 16))
+        } else if data_type == DT_SLONG {
+            RecordValue::I32(Records::read_value_i32(&data, 0))
+        } else {
+            RecordValue::U32(Records::read_value_u32(&data, 0))
+        };
+
+        Some(Record {
+            code,
+            lri: code & 0x00FFFF00,
+            channel: (code & 0xFF) as u8,
+            data_type,
+            date,
+            value,
+        })
+    }
 }
 
 impl Inverter {
     pub fn new(address: SocketAddr) -> Self {
         Self {
             address,
+            device_susy_id: 0,
+            device_serial: 0,
             packet_id: 0,
             susy_id: gen_susy_id(),
             serial: gen_serial(),
+            credentials: None,
+            failures: 0,
+            session_expired: false,
+            login_backoff: Inverter::MIN_LOGIN_BACKOFF,
+            next_login: Instant::now(),
         }
     }
 
@@ -108,11 +615,12 @@ impl Inverter {
         buffer.write_u8(long_words);
         buffer.write_u8(control);
 
+        // Address the device once it is known, so other devices behind a gateway ignore the request.
         //SUSy id
-        buffer.write_u16(0xffff);
+        buffer.write_u16(if self.device_susy_id != 0 { self.device_susy_id } else { 0xffff });
 
         //Serial
-        buffer.write_u32(0xffffffff);
+        buffer.write_u32(if self.device_serial != 0 { self.device_serial } else { 0xffffffff });
 
         buffer.write_u16(control_2);
 
@@ -150,7 +658,18 @@ impl Inverter {
         unsafe { &*(buf as *const [MaybeUninit<u8>] as *const [u8]) }
     }
 
-    pub fn login(&mut self, socket: &Socket, password: &str) -> Result<u16, InverterError> {
+    pub fn login(
+        &mut self,
+        socket: &Socket,
+        user_group: UserGroup,
+        password: &str,
+    ) -> Result<u16, InverterError> {
+        let password_bytes = password.as_bytes();
+        if password_bytes.len() > Inverter::PASSWORD_LENGTH {
+            return Err(InverterError::PasswordTooLong);
+        }
+        self.credentials = Some((user_group, password.to_string()));
+
         let mut buffer = ByteBuffer::new();
         buffer.set_endian(LittleEndian);
 
@@ -160,7 +679,7 @@ impl Inverter {
 
         buffer.write_u32(0xFFFD040C);
 
-        buffer.write_u32(0x07);
+        buffer.write_u32(user_group.id());
         buffer.write_u32(0x00000384);
 
         let start = SystemTime::now();
@@ -171,13 +690,12 @@ impl Inverter {
         buffer.write_u32(since_the_epoch.as_secs() as u32);
         buffer.write_u32(0);
 
-        let enc_char = 0x88; //admin:0xbb
-        let password_bytes = password.as_bytes();
+        let enc_char = user_group.enc_char();
 
         for byte in password_bytes {
-            buffer.write_u8(byte + enc_char);
+            buffer.write_u8(byte.wrapping_add(enc_char));
         }
-        for _i in password_bytes.len()..12 {
+        for _i in password_bytes.len()..Inverter::PASSWORD_LENGTH {
             buffer.write_u8(enc_char);
         }
 
@@ -192,76 +710,17 @@ impl Inverter {
             }
         }
 
-        let mut buf = [MaybeUninit::new(0_u8); 500];
-        match socket.recv_from(buf.as_mut()) {
-            Ok((len, remote_addr)) => {
-                if remote_addr.as_socket().unwrap().eq(&self.address) {
-                    let mut buffer =
-                        ByteBuffer::from_bytes(unsafe { self.assume_init(&buf[0..len]) });
-                    buffer.set_endian(LittleEndian);
-                    //L1
-                    let l1_magic_number = buffer.read_u32();
-                    if l1_magic_number != 0x00414D53 {
-                        return Err(InverterError {
-                            message: "Packet does not start with SMA",
-                        });
-                    }
-                    buffer.read_u32();
-                    buffer.read_u32();
-                    let packet_length = buffer.read_u16();
-
-                    //L2
-                    let l2_magic_number = buffer.read_u32();
-                    let _long_words = buffer.read_u8();
-                    let _ctrl = buffer.read_u8();
-
-                    if packet_length > 0 {
-                        if l2_magic_number == 0x65601000 {
-                            let _dest_susy_id = buffer.read_u16();
-                            let _dest_serial = buffer.read_u32();
-                            buffer.read_u16();
-
-                            let _source_susy_id = buffer.read_u16();
-                            let _source_serial = buffer.read_u32();
-                            buffer.read_u16();
-
-                            let error_code = buffer.read_u16();
-                            let _fragment_id = buffer.read_u16();
-                            let packet_id = buffer.read_u16();
-
-                            if packet_id & 0x7FFF == self.packet_id as u16 {
-                                if error_code == 0 {
-                                    Ok(error_code)
-                                } else {
-                                    Err(InverterError {
-                                        message: "Login failed.",
-                                    })
-                                }
-                            } else {
-                                Err(InverterError {
-                                    message: "Invalid packet id.",
-                                })
-                            }
-                        } else {
-                            Err(InverterError {
-                                message: "Magic bytes do not match.",
-                            })
-                        }
-                    } else {
-                        Err(InverterError {
-                            message: "Packet length is zero.",
-                        })
-                    }
-                } else {
-                    Err(InverterError {
-                        message: "Sent from wrong address.",
-                    })
-                }
-            }
-            Err(err) => {
-                log!(format!("{}", err));
-                Err(InverterError { message: "error" })
-            }
+        let deadline = Instant::now() + Inverter::RESPONSE_TIMEOUT;
+        let response = self.receive(socket, deadline)?;
+        if response.error_code == 0 {
+            self.device_susy_id = response.source_susy_id;
+            self.device_serial = response.source_serial;
+            self.failures = 0;
+            self.session_expired = false;
+            self.login_backoff = Inverter::MIN_LOGIN_BACKOFF;
+            Ok(response.error_code)
+        } else {
+            Err(InverterError::LoginRejected)
         }
     }
 
@@ -287,9 +746,30 @@ impl Inverter {
         }
     }
 
+    const PASSWORD_LENGTH: usize = 12;
+
+    /// Error code of requests sent without a valid session, e.g. after the inverter restarted.
+    /// Not part of SMA's published Speedwire documentation, SBFspot handles it the same way.
+    const NOT_LOGGED_IN: u16 = 0x0017;
+    /// Failed requests in a row after which the session is considered lost.
+    const MAX_FAILURES: u32 = 3;
+    const MIN_LOGIN_BACKOFF: Duration = Duration::from_secs(10);
+    const MAX_LOGIN_BACKOFF: Duration = Duration::from_secs(600);
+
+    /// Overall time to wait for a complete response including all of its fragments.
+    const RESPONSE_TIMEOUT: Duration = Duration::from_secs(2);
+    /// L1 and L2 headers up to and including the packet id.
+    const RESPONSE_HEADER_LENGTH: usize = 42;
+
+    const IDENTIFY: DataType = DataType {
+        command: 0x00000200,
+        first: 0,
+        last: 0,
+    };
+
     const SPOT_DC_VOLTAGE: DataType = DataType {
         command: 0x53800200,
-        first: 0x00451F00,
+        first: 0x00251E00,
         last: 0x004521FF,
     };
     const SPOT_AC_VOLTAGE: DataType = DataType {
@@ -297,6 +777,46 @@ impl Inverter {
         first: 0x00464800,
         last: 0x004655FF,
     };
+    const SPOT_AC_POWER: DataType = DataType {
+        command: 0x51000200,
+        first: 0x00464000,
+        last: 0x004642FF,
+    };
+    const SPOT_AC_TOTAL_POWER: DataType = DataType {
+        command: 0x51000200,
+        first: 0x00263F00,
+        last: 0x00263FFF,
+    };
+    const GRID_FREQUENCY: DataType = DataType {
+        command: 0x51000200,
+        first: 0x00465700,
+        last: 0x004657FF,
+    };
+    const GRID_POWER: DataType = DataType {
+        command: 0x51000200,
+        first: 0x00665900,
+        last: 0x00666AFF,
+    };
+    const DEVICE_INFO: DataType = DataType {
+        command: 0x58000200,
+        first: 0x00821E00,
+        last: 0x008234FF,
+    };
+    const DEVICE_STATUS: DataType = DataType {
+        command: 0x51800200,
+        first: 0x00214800,
+        last: 0x002148FF,
+    };
+    const GRID_RELAY_STATUS: DataType = DataType {
+        command: 0x51800200,
+        first: 0x00416400,
+        last: 0x004164FF,
+    };
+    const INVERTER_TEMPERATURE: DataType = DataType {
+        command: 0x52000200,
+        first: 0x00237700,
+        last: 0x002377FF,
+    };
     const BATTERY_CHARGE_STATUS: DataType = DataType {
         command: 0x51000200,
         first: 0x00295A00,
@@ -313,11 +833,64 @@ impl Inverter {
         last: 0x002622FF,
     };
 
-    fn get_data(
-        &mut self,
-        socket: &Socket,
-        data_type: &DataType,
-    ) -> Result<ByteBuffer, InverterError> {
+    const BATTERY_OPERATING_STATE: DataType = DataType {
+        command: 0x51800200,
+        first: 0x00495E00,
+        last: 0x00495EFF,
+    };
+    const BATTERY_POWER: DataType = DataType {
+        command: 0x51000200,
+        first: 0x00496900,
+        last: 0x00496AFF,
+    };
+    const BATTERY_ENERGY: DataType = DataType {
+        command: 0x54000200,
+        first: 0x00496700,
+        last: 0x004968FF,
+    };
+    const BATTERY_DAILY_ENERGY: DataType = DataType {
+        command: 0x54000200,
+        first: 0x00496B00,
+        last: 0x00496CFF,
+    };
+    const GRID_EXCHANGE_POWER: DataType = DataType {
+        command: 0x51000200,
+        first: 0x00463600,
+        last: 0x004637FF,
+    };
+    const GRID_EXCHANGE_ENERGY: DataType = DataType {
+        command: 0x54000200,
+        first: 0x00462400,
+        last: 0x004625FF,
+    };
+    const OPERATION_TIME: DataType = DataType {
+        command: 0x54000200,
+        first: 0x00462E00,
+        last: 0x00462FFF,
+    };
+
+    /// Asks the device for its SUSy id and serial. This works without login, so it is used to
+    /// tell inverters apart from other devices answering discovery. The class is only known from
+    /// the SUSy id here, the nameplate is not reported before login.
+    pub fn identify(&mut self, socket: &Socket) -> Result<DiscoveredDevice, InverterError> {
+        self.send_request(socket, &Inverter::IDENTIFY);
+        let deadline = Instant::now() + Inverter::RESPONSE_TIMEOUT;
+        let response = self.receive(socket, deadline)?;
+        if response.error_code != 0 {
+            return Err(InverterError::DeviceErrorCode(response.error_code));
+        }
+        self.device_susy_id = response.source_susy_id;
+        self.device_serial = response.source_serial;
+
+        Ok(DiscoveredDevice {
+            address: self.address,
+            susy_id: self.device_susy_id,
+            serial: self.device_serial,
+            device_class: DeviceClass::new(self.device_susy_id, None),
+        })
+    }
+
+    fn send_request(&mut self, socket: &Socket, data_type: &DataType) {
         let mut buffer = ByteBuffer::new();
         buffer.set_endian(LittleEndian);
 
@@ -337,443 +910,811 @@ impl Inverter {
                 log!(format!("{}", error));
             }
         }
+    }
+
+    fn get_data(
+        &mut self,
+        socket: &Socket,
+        data_type: &DataType,
+    ) -> Result<ByteBuffer, InverterError> {
+        self.send_request(socket, data_type);
+
+        // Wide ranges are answered with several fragments, the fragment id counts down to zero.
+        // The records of all fragments are collected into one buffer.
+        let mut records = ByteBuffer::new();
+        records.set_endian(LittleEndian);
+        let deadline = Instant::now() + Inverter::RESPONSE_TIMEOUT;
+        let mut expected_fragment_id = None;
+
+        loop {
+            let response = self.receive(socket, deadline)?;
+            match Inverter::append_fragment(&mut records, response, expected_fragment_id, data_type)? {
+                Some(fragment_id) => expected_fragment_id = Some(fragment_id),
+                None => return Ok(records),
+            }
+        }
+    }
 
-        let mut buf = [MaybeUninit::new(0_u8); 1024];
-        match socket.recv_from(buf.as_mut()) {
-            Ok((len, remote_addr)) => {
-                if remote_addr.as_socket().unwrap().eq(&self.address) {
-                    let mut buffer =
-                        ByteBuffer::from_bytes(unsafe { self.assume_init(&buf[0..len]) });
-                    buffer.set_endian(LittleEndian);
-                    //L1
-                    let l1_magic_number = buffer.read_u32();
-                    if l1_magic_number != 0x00414D53 {
-                        return Err(InverterError {
-                            message: "Wrong magic number.",
-                        });
+    /// Appends the records of one fragment to `records` and returns the id of the next fragment,
+    /// `None` after the last one. A lost or repeated fragment would leave the records incomplete.
+    fn append_fragment(
+        records: &mut ByteBuffer,
+        mut response: Response,
+        expected_fragment_id: Option<u16>,
+        data_type: &DataType,
+    ) -> Result<Option<u16>, InverterError> {
+        if let Some(fragment_id) = expected_fragment_id
+            && response.fragment_id != fragment_id
+        {
+            return Err(InverterError::Truncated);
+        }
+        if response.error_code == 21 {
+            return Err(InverterError::Unsupported(data_type.first));
+        } else if response.error_code != 0 {
+            return Err(InverterError::DeviceErrorCode(response.error_code));
+        }
+        if response.buffer.len() < response.buffer.get_rpos() + 12 {
+            return Err(InverterError::Truncated);
+        }
+
+        // Every fragment repeats command and range, the trailing end marker is shorter than a
+        // record and dropped.
+        let record_size = data_type.record_size();
+        let buffer = &mut response.buffer;
+        buffer.read_bytes(12);
+        let count = (buffer.len() - buffer.get_rpos()) / record_size;
+        records.write_bytes(&buffer.read_bytes(count * record_size));
+
+        match response.fragment_id {
+            0 => Ok(None),
+            fragment_id => Ok(Some(fragment_id - 1)),
+        }
+    }
+
+    /// Receives datagrams until the reply to the current packet id arrives or the deadline
+    /// passes. Late replies to earlier requests and packets of other devices sharing the socket
+    /// are discarded.
+    fn receive(&mut self, socket: &Socket, deadline: Instant) -> Result<Response, InverterError> {
+        let mut buf = [MaybeUninit::new(0_u8); 2048];
+        loop {
+            // Wait no longer than the deadline, even though every receive blocks.
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(InverterError::Timeout);
+            }
+            socket.set_read_timeout(Some(remaining))?;
+            match socket.recv_from(buf.as_mut()) {
+                Ok((len, remote_addr)) => {
+                    // Datagrams that do not fit are cut off by the kernel.
+                    if len == buf.len() {
+                        log!(format!("Inverter {} discarding packet from {:?}: {}",
+                            self.address, remote_addr.as_socket(), InverterError::Truncated));
+                        continue;
                     }
-                    buffer.read_u32();
-                    buffer.read_u32();
-                    let packet_length = buffer.read_u16();
-                    //L2
-                    let l2_magic_number = buffer.read_u32();
-                    let _long_words = buffer.read_u8();
-                    let _ctrl = buffer.read_u8();
-
-                    if packet_length > 0 {
-                        if l2_magic_number == 0x65601000 {
-                            let _dest_susy_id = buffer.read_u16();
-                            let _dest_serial = buffer.read_u32();
-                            buffer.read_u16();
-
-                            let _source_susy_id = buffer.read_u16();
-                            let _source_serial = buffer.read_u32();
-                            buffer.read_u16();
-
-                            let error_code = buffer.read_u16();
-                            let _fragment_id = buffer.read_u16();
-                            let packet_id = buffer.read_u16();
-
-                            if packet_id & 0x7FFF == self.packet_id as u16 {
-                                if error_code == 0 {
-                                    buffer.read_bytes(12);
-
-                                    Ok(buffer)
-                                } else if error_code == 21 {
-                                    Err(InverterError {
-                                        message: "Unsupported",
-                                    })
-                                } else {
-                                    Err(InverterError {
-                                        message: "Error code",
-                                    })
-                                }
-                            } else {
-                                Err(InverterError {
-                                    message: "Wrong packed id.",
-                                })
-                            }
-                        } else {
-                            Err(InverterError {
-                                message: "Wrong magic number.",
-                            })
+                    let remote_addr = remote_addr.as_socket();
+                    let data = unsafe { self.assume_init(&buf[0..len]) }.to_vec();
+                    match self.parse_response(&data, remote_addr) {
+                        Ok(response) => return Ok(response),
+                        Err(error) => {
+                            log!(format!(
+                                "Inverter {} discarding packet from {:?}: {}",
+                                self.address, remote_addr, error
+                            ));
                         }
-                    } else {
-                        Err(InverterError {
-                            message: "Zero packet length.",
-                        })
                     }
-                } else {
-                    Err(InverterError {
-                        message: "Wrong source address.",
-                    })
                 }
+                Err(err) => match InverterError::from(err) {
+                    InverterError::Timeout => {}
+                    error => return Err(error),
+                },
             }
-            Err(err) => {
-                log!(format!("{}", err));
-                Err(InverterError { message: "Error" })
+        }
+    }
+
+    fn parse_response(
+        &self,
+        data: &[u8],
+        remote_addr: Option<SocketAddr>,
+    ) -> Result<Response, InverterError> {
+        if remote_addr.ne(&Some(self.address)) {
+            return Err(InverterError::WrongSource);
+        }
+        if data.len() < Inverter::RESPONSE_HEADER_LENGTH {
+            return Err(InverterError::Truncated);
+        }
+        let mut buffer = ByteBuffer::from_bytes(data);
+        buffer.set_endian(LittleEndian);
+        //L1
+        let l1_magic_number = buffer.read_u32();
+        if l1_magic_number != 0x00414D53 {
+            return Err(InverterError::BadMagic);
+        }
+        buffer.read_u32();
+        buffer.read_u32();
+        let packet_length = buffer.read_u16();
+        //L2
+        let l2_magic_number = buffer.read_u32();
+        let _long_words = buffer.read_u8();
+        let _ctrl = buffer.read_u8();
+
+        if packet_length == 0 {
+            return Err(InverterError::Truncated);
+        }
+        if l2_magic_number != 0x65601000 {
+            return Err(InverterError::BadMagic);
+        }
+        let _dest_susy_id = buffer.read_u16();
+        let _dest_serial = buffer.read_u32();
+        buffer.read_u16();
+
+        let source_susy_id = buffer.read_u16();
+        let source_serial = buffer.read_u32();
+        buffer.read_u16();
+
+        // Before the first login the serial of the device is not known yet.
+        if self.device_serial != 0 && source_serial != self.device_serial {
+            return Err(InverterError::WrongSource);
+        }
+
+        let error_code = buffer.read_u16();
+        let fragment_id = buffer.read_u16();
+        let packet_id = buffer.read_u16();
+
+        if packet_id & 0x7FFF != self.packet_id as u16 {
+            return Err(InverterError::WrongPacketId);
+        }
+
+        Ok(Response {
+            buffer,
+            source_susy_id,
+            source_serial,
+            error_code,
+            fragment_id,
+        })
+    }
+
+    fn get_records(&mut self, socket: &Socket, data_type: &DataType) -> Result<Records, InverterError> {
+        match self.get_data(socket, data_type) {
+            Ok(buffer) => {
+                self.failures = 0;
+                Ok(Records {
+                    buffer,
+                    record_size: data_type.record_size(),
+                })
+            }
+            // The device answered, the session is fine.
+            Err(InverterError::Unsupported(lri)) => {
+                self.failures = 0;
+                Err(InverterError::Unsupported(lri))
+            }
+            Err(InverterError::DeviceErrorCode(Inverter::NOT_LOGGED_IN)) => {
+                self.session_expired = true;
+                Err(InverterError::DeviceErrorCode(Inverter::NOT_LOGGED_IN))
+            }
+            Err(InverterError::DeviceErrorCode(code)) => {
+                self.failures = 0;
+                Err(InverterError::DeviceErrorCode(code))
+            }
+            Err(error) => {
+                self.failures += 1;
+                Err(error)
             }
         }
     }
 
-    pub fn get_battery_charge_status(&mut self, socket: &Socket) -> Result<[u8; 3], InverterError> {
-        match self.get_data(socket, &Inverter::BATTERY_CHARGE_STATUS) {
-            Ok(mut buffer) => {
-                let mut battery_charge: [u8; 3] = [0, 0, 0];
+    /// Like `get_records`, but `None` if the device does not support the range, for optional
+    /// values queried in addition to the main range of a getter.
+    fn get_optional_records(
+        &mut self,
+        socket: &Socket,
+        data_type: &DataType,
+    ) -> Result<Option<Records>, InverterError> {
+        match self.get_records(socket, data_type) {
+            Ok(records) => Ok(Some(records)),
+            Err(InverterError::Unsupported(_)) => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
 
-                while buffer.len() > buffer.get_rpos() {
-                    let code = buffer.read_u32();
-                    if code == 0 {
-                        return Ok(battery_charge);
-                    }
-                    let lri = code & 0x00FFFF00;
-                    let _data_type = code >> 24;
-
-                    if lri == BatChaStt as u32 && battery_charge[0] == 0 {
-                        let _date = buffer.read_u32();
-                        let value = buffer.read_u32();
-                        battery_charge[0] = value as u8;
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    } else if lri == BatChaStt as u32 && battery_charge[1] == 0 {
-                        let _date = buffer.read_u32();
-                        let value = buffer.read_u32();
-                        battery_charge[1] = value as u8;
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    } else if lri == BatChaStt as u32 && battery_charge[2] == 0 {
-                        let _date = buffer.read_u32();
-                        let value = buffer.read_u32();
-                        battery_charge[2] = value as u8;
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    } else {
-                        let _date = buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    }
+    /// True when the device reported a lost session or stopped answering, e.g. after a reboot.
+    pub fn needs_login(&self) -> bool {
+        self.session_expired || self.failures >= Inverter::MAX_FAILURES
+    }
+
+    /// Logs in again with the credentials of the last login. Failed attempts are retried with
+    /// exponential backoff, `None` is returned while waiting for the next attempt.
+    pub fn relogin(&mut self, socket: &Socket) -> Option<Result<u16, InverterError>> {
+        if Instant::now() < self.next_login {
+            return None;
+        }
+        let (user_group, password) = self.credentials.clone()?;
+        let result = self.login(socket, user_group, password.as_str());
+        if result.is_err() {
+            self.next_login = Instant::now() + self.login_backoff;
+            self.login_backoff = (self.login_backoff * 2).min(Inverter::MAX_LOGIN_BACKOFF);
+        }
+        Some(result)
+    }
+
+    /// Software versions are packed as type, build, minor and major with BCD coded minor and major.
+    fn read_firmware_version(value: u32) -> String {
+        let [release_type, build, minor, major] = value.to_le_bytes();
+        let release_type = match "NEABRS".chars().nth(release_type as usize) {
+            Some(release_type) => release_type.to_string(),
+            None => release_type.to_string(),
+        };
+        format!("{:x}{:x}.{:x}{:x}.{:02}.{}", major >> 4, major & 0x0F, minor >> 4, minor & 0x0F, build, release_type)
+    }
+
+    pub fn get_device_info(&mut self, socket: &Socket) -> Result<DeviceInfo, InverterError> {
+        let mut device_info = DeviceInfo {
+            name: String::new(),
+            device_class: None,
+            model: None,
+            firmware: String::new(),
+        };
+
+        for record in self.get_records(socket, &Inverter::DEVICE_INFO)? {
+            match record.value {
+                RecordValue::String(name) if record.lri == NameplateLocation as u32 => {
+                    device_info.name = name;
+                }
+                RecordValue::Status(tag) if record.lri == NameplateMainModel as u32 => {
+                    device_info.device_class = tag;
+                }
+                RecordValue::Status(tag) if record.lri == NameplateModel as u32 => {
+                    device_info.model = tag;
+                }
+                RecordValue::U32(Some(value)) if record.lri == NameplatePkgRev as u32 => {
+                    device_info.firmware = Inverter::read_firmware_version(value);
                 }
-                Ok(battery_charge)
+                _ => {}
             }
-            Err(error) => Err(InverterError {
-                message: error.message,
-            }),
         }
+        Ok(device_info)
     }
 
-    pub fn get_battery_info(&mut self, socket: &Socket) -> Result<BatteryInfo, InverterError> {
-        match self.get_data(socket, &Inverter::BATTERY_INFO) {
-            Ok(mut buffer) => {
-                let mut battery_info = BatteryInfo {
-                    temperature: [0, 0, 0],
-                    voltage: [0, 0, 0],
-                    current: [0, 0, 0],
-                };
-
-                while buffer.len() >= buffer.get_rpos()+(7*4) {
-                    let code = buffer.read_u32();
-                    if code == 0 {
-                        return Ok(battery_info);
-                    }
-                    let lri = code & 0x00FFFF00;
-                    let _data_type = code >> 24;
-
-                    if lri == BatTmpVal as u32 && battery_info.temperature[0] == 0 {
-                        let _date = buffer.read_u32();
-                        let value = buffer.read_u32();
-                        battery_info.temperature[0] = value as u16;
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    } else if lri == BatTmpVal as u32 && battery_info.temperature[1] == 0 {
-                        let _date = buffer.read_u32();
-                        let value = buffer.read_u32();
-                        battery_info.temperature[1] = value as u16;
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    } else if lri == BatTmpVal as u32 && battery_info.temperature[2] == 0 {
-                        let _date = buffer.read_u32();
-                        let value = buffer.read_u32();
-                        battery_info.temperature[2] = value as u16;
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    } else if lri == BatAmp as u32 && battery_info.current[0] == 0 {
-                        let _date = buffer.read_u32();
-                        let value = buffer.read_i32();
-                        battery_info.current[0] = value as i16;
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    } else if lri == BatAmp as u32 && battery_info.current[1] == 0 {
-                        let _date = buffer.read_u32();
-                        let value = buffer.read_i32();
-                        battery_info.current[1] = value as i16;
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    } else if lri == BatAmp as u32 && battery_info.current[2] == 0 {
-                        let _date = buffer.read_u32();
-                        let value = buffer.read_i32();
-                        battery_info.current[2] = value as i16;
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    } else if lri == BatVol as u32 && battery_info.voltage[0] == 0 {
-                        let _date = buffer.read_u32();
-                        let mut value = buffer.read_u32();
-                        if value == 65535 {
-                            value = 0;
-                        }
-                        battery_info.voltage[0] = value as u16;
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    } else if lri == BatVol as u32 && battery_info.voltage[1] == 0 {
-                        let _date = buffer.read_u32();
-                        let mut value = buffer.read_u32();
-                        if value == 65535 {
-                            value = 0;
-                        }
-                        battery_info.voltage[1] = value as u16;
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    } else if lri == BatVol as u32 && battery_info.voltage[2] == 0 {
-                        let _date = buffer.read_u32();
-                        let mut value = buffer.read_u32();
-                        if value == 65535 {
-                            value = 0;
-                        }
-                        battery_info.voltage[2] = value as u16;
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    } else {
-                        let _date = buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    }
+    pub fn get_device_status(&mut self, socket: &Socket) -> Result<StatusInfo, InverterError> {
+        let mut status_info = StatusInfo {
+            device_status: None,
+            grid_relay: None,
+        };
+
+        for record in self.get_records(socket, &Inverter::DEVICE_STATUS)? {
+            if record.lri == OperationHealth as u32 {
+                status_info.device_status = record.value.as_tag();
+            }
+        }
+
+        // Not every device has a grid relay, e.g. battery inverters.
+        if let Some(records) = self.get_optional_records(socket, &Inverter::GRID_RELAY_STATUS)? {
+            for record in records {
+                if record.lri == OperationGriSwStt as u32 {
+                    status_info.grid_relay = record.value.as_tag();
                 }
-                Ok(battery_info)
             }
-            Err(error) => Err(InverterError {
-                message: error.message,
-            }),
         }
+        Ok(status_info)
     }
 
-    pub fn get_dc_voltage(&mut self, socket: &Socket) -> Result<DCInfo, InverterError> {
-        match self.get_data(socket, &Inverter::SPOT_DC_VOLTAGE) {
-            Ok(mut buffer) => {
-                let mut dc_info = DCInfo {
-                    voltage: [0, 0],
-                    current: [0, 0],
-                };
-
-                while buffer.len() >= buffer.get_rpos()+(7*4) {
-                    let code = buffer.read_u32();
-                    if code == 0 {
-                        return Ok(dc_info);
-                    }
-                    let lri = code & 0x00FFFF00;
-                    let _data_type = code >> 24;
-
-                    if lri == DcMsVol as u32 && dc_info.voltage[0] == 0 {
-                        let _date = buffer.read_u32();
-                        let value = buffer.read_u32();
-                        dc_info.voltage[0] = value as u16;
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    } else if lri == DcMsVol as u32 && dc_info.voltage[1] == 0 {
-                        let _date = buffer.read_u32();
-                        let value = buffer.read_u32();
-                        dc_info.voltage[1] = value as u16;
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    } else if lri == DcMsAmp as u32 && dc_info.current[0] == 0 {
-                        let _date = buffer.read_u32();
-                        let value = buffer.read_u32();
-                        dc_info.current[0] = value as u16;
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    } else if lri == DcMsAmp as u32 && dc_info.current[1] == 0 {
-                        let _date = buffer.read_u32();
-                        let value = buffer.read_u32();
-                        dc_info.current[1] = value as u16;
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    } else {
-                        log!(format!("unhandled (dc voltage): {:x}", lri));
-                        break;
-                    }
+    pub fn get_inverter_temperature(&mut self, socket: &Socket) -> Result<Option<Measurement>, InverterError> {
+        let mut temperature = None;
+
+        for record in self.get_records(socket, &Inverter::INVERTER_TEMPERATURE)? {
+            if record.lri == CoolsysTmpVal as u32 {
+                temperature = record.value.as_measurement(100);
+            } else {
+                log!(format!("unhandled (inverter temperature): {}", record));
+            }
+        }
+        Ok(temperature)
+    }
+
+    pub fn get_battery_state(&mut self, socket: &Socket) -> Result<BatteryStateInfo, InverterError> {
+        let mut bs_info = BatteryStateInfo {
+            operating_state: BTreeMap::new(),
+            charge_power: BTreeMap::new(),
+            discharge_power: BTreeMap::new(),
+        };
+
+        for record in self.get_records(socket, &Inverter::BATTERY_OPERATING_STATE)? {
+            if record.lri == BatOpStt as u32 && let Some(tag) = record.value.as_tag() {
+                bs_info.operating_state.insert(record.channel, tag);
+            }
+        }
+
+        // Keep the operating state if the device does not report battery power.
+        if let Some(records) = self.get_optional_records(socket, &Inverter::BATTERY_POWER)? {
+            for record in records {
+                if record.lri == BatDiagChrgW as u32 {
+                    bs_info.charge_power.insert(record.channel, record.value.as_measurement(1));
+                } else if record.lri == BatDiagDschW as u32 {
+                    bs_info.discharge_power.insert(record.channel, record.value.as_measurement(1));
+                } else {
+                    log!(format!("unhandled (battery power): {}", record));
                 }
-                Ok(dc_info)
             }
-            Err(error) => Err(InverterError {
-                message: error.message,
-            }),
         }
+        Ok(bs_info)
+    }
+
+    pub fn get_battery_charge_status(&mut self, socket: &Socket) -> Result<BTreeMap<u8, Option<Measurement>>, InverterError> {
+        let mut battery_charge = BTreeMap::new();
+
+        for record in self.get_records(socket, &Inverter::BATTERY_CHARGE_STATUS)? {
+            if record.lri == BatChaStt as u32 {
+                battery_charge.insert(record.channel, record.value.as_measurement(1));
+            }
+        }
+        Ok(battery_charge)
+    }
+
+    pub fn get_battery_info(&mut self, socket: &Socket) -> Result<BatteryInfo, InverterError> {
+        let mut battery_info = BatteryInfo {
+            temperature: BTreeMap::new(),
+            voltage: BTreeMap::new(),
+            current: BTreeMap::new(),
+            state_of_health: BTreeMap::new(),
+            rated_capacity: BTreeMap::new(),
+            charge_cycles: BTreeMap::new(),
+            charged_ah: BTreeMap::new(),
+            discharged_ah: BTreeMap::new(),
+        };
+
+        for record in self.get_records(socket, &Inverter::BATTERY_INFO)? {
+            let channel = record.channel;
+            let value = &record.value;
+
+            if record.lri == BatTmpVal as u32 {
+                battery_info.temperature.insert(channel, value.as_measurement(10));
+            } else if record.lri == BatAmp as u32 {
+                battery_info.current.insert(channel, value.as_measurement(1000));
+            } else if record.lri == BatVol as u32 {
+                battery_info.voltage.insert(channel, value.as_measurement(100));
+            } else if record.lri == BatDiagActlCapacNom as u32 {
+                battery_info.state_of_health.insert(channel, value.as_measurement(1));
+            } else if record.lri == BatCapacRtgWh as u32 {
+                battery_info.rated_capacity.insert(channel, value.as_measurement(1));
+            } else if record.lri == BatDiagCapacThrpCnt as u32 {
+                battery_info.charge_cycles.insert(channel, value.as_u32());
+            } else if record.lri == BatDiagTotAhIn as u32 {
+                battery_info.charged_ah.insert(channel, value.as_u32());
+            } else if record.lri == BatDiagTotAhOut as u32 {
+                battery_info.discharged_ah.insert(channel, value.as_u32());
+            }
+        }
+        Ok(battery_info)
+    }
+
+    pub fn get_dc_voltage(&mut self, socket: &Socket) -> Result<DCInfo, InverterError> {
+        let mut dc_info = DCInfo {
+            voltage: BTreeMap::new(),
+            current: BTreeMap::new(),
+            power: BTreeMap::new(),
+        };
+
+        for record in self.get_records(socket, &Inverter::SPOT_DC_VOLTAGE)? {
+            let channel = record.channel;
+            let value = &record.value;
+
+            if record.lri == DcMsVol as u32 {
+                dc_info.voltage.insert(channel, value.as_measurement(100));
+            } else if record.lri == DcMsAmp as u32 {
+                dc_info.current.insert(channel, value.as_measurement(1000));
+            } else if record.lri == DcMsWatt as u32 {
+                dc_info.power.insert(channel, value.as_measurement(1));
+            } else {
+                log!(format!("unhandled (dc voltage): {}", record));
+            }
+        }
+        Ok(dc_info)
     }
 
     pub fn get_ac_voltage(&mut self, socket: &Socket) -> Result<ACInfo, InverterError> {
-        match self.get_data(socket, &Inverter::SPOT_AC_VOLTAGE) {
-            Ok(mut buffer) => {
-                let mut ac_info = ACInfo {
-                    voltage: [0, 0, 0],
-                    current: [0, 0, 0],
-                };
-
-                while buffer.len() >= buffer.get_rpos()+(7*4) {
-                    let code = buffer.read_u32();
-                    if code == 0 {
-                        return Ok(ac_info);
-                    }
-                    let lri = code & 0x00FFFF00;
-                    let _data_type = code >> 24;
-
-                    if lri == AcMsVol0 as u32 && ac_info.voltage[0] == 0 {
-                        let _date = buffer.read_u32();
-                        let value = buffer.read_u32();
-                        ac_info.voltage[0] = value as u16;
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    } else if lri == AcMsVol1 as u32 && ac_info.voltage[1] == 0 {
-                        let _date = buffer.read_u32();
-                        let value = buffer.read_u32();
-                        ac_info.voltage[1] = value as u16;
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    } else if lri == AcMsVol2 as u32 && ac_info.voltage[2] == 0 {
-                        let _date = buffer.read_u32();
-                        let value = buffer.read_u32();
-                        ac_info.voltage[2] = value as u16;
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    } else if lri == AcMsAmp0 as u32 && ac_info.current[0] == 0 {
-                        let _date = buffer.read_u32();
-                        let value = buffer.read_u32();
-                        ac_info.current[0] = value as u16;
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    } else if lri == AcMsAmp1 as u32 && ac_info.current[1] == 0 {
-                        let _date = buffer.read_u32();
-                        let value = buffer.read_u32();
-                        ac_info.current[1] = value as u16;
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    } else if lri == AcMsAmp2 as u32 && ac_info.current[2] == 0 {
-                        let _date = buffer.read_u32();
-                        let value = buffer.read_u32();
-                        ac_info.current[2] = value as u16;
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    } else {
-                        log!(format!("unhandled (ac voltage): {:x}", lri));
-                        let _date = buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    }
+        let mut ac_info = ACInfo {
+            voltage: BTreeMap::new(),
+            current: BTreeMap::new(),
+        };
+
+        for record in self.get_records(socket, &Inverter::SPOT_AC_VOLTAGE)? {
+            let value = &record.value;
+
+            if record.lri == AcMsVol0 as u32 {
+                ac_info.voltage.insert(1, value.as_measurement(100));
+            } else if record.lri == AcMsVol1 as u32 {
+                ac_info.voltage.insert(2, value.as_measurement(100));
+            } else if record.lri == AcMsVol2 as u32 {
+                ac_info.voltage.insert(3, value.as_measurement(100));
+            } else if record.lri == AcMsAmp0 as u32 {
+                ac_info.current.insert(1, value.as_measurement(1000));
+            } else if record.lri == AcMsAmp1 as u32 {
+                ac_info.current.insert(2, value.as_measurement(1000));
+            } else if record.lri == AcMsAmp2 as u32 {
+                ac_info.current.insert(3, value.as_measurement(1000));
+            } else {
+                log!(format!("unhandled (ac voltage): {}", record));
+            }
+        }
+        Ok(ac_info)
+    }
+
+    pub fn get_ac_power(&mut self, socket: &Socket) -> Result<ACPowerInfo, InverterError> {
+        let mut ac_power_info = ACPowerInfo {
+            power: BTreeMap::new(),
+            total: None,
+        };
+
+        for record in self.get_records(socket, &Inverter::SPOT_AC_POWER)? {
+            let value = record.value.as_measurement(1);
+
+            if record.lri == AcMsWatt0 as u32 {
+                ac_power_info.power.insert(1, value);
+            } else if record.lri == AcMsWatt1 as u32 {
+                ac_power_info.power.insert(2, value);
+            } else if record.lri == AcMsWatt2 as u32 {
+                ac_power_info.power.insert(3, value);
+            } else {
+                log!(format!("unhandled (ac power): {}", record));
+            }
+        }
+
+        // Keep the phase values if the device has no total.
+        if let Some(records) = self.get_optional_records(socket, &Inverter::SPOT_AC_TOTAL_POWER)? {
+            for record in records {
+                if record.lri == GridMsTotW as u32 {
+                    ac_power_info.total = record.value.as_measurement(1);
+                } else {
+                    log!(format!("unhandled (ac total power): {}", record));
+                }
+            }
+        }
+        Ok(ac_power_info)
+    }
+
+    pub fn get_grid_info(&mut self, socket: &Socket) -> Result<GridInfo, InverterError> {
+        let mut grid_info = GridInfo {
+            frequency: None,
+            reactive_power: BTreeMap::new(),
+            apparent_power: BTreeMap::new(),
+            power_factor: None,
+        };
+
+        for record in self.get_records(socket, &Inverter::GRID_FREQUENCY)? {
+            if record.lri == GridMsHz as u32 {
+                grid_info.frequency = record.value.as_measurement(100);
+            } else {
+                log!(format!("unhandled (grid frequency): {}", record));
+            }
+        }
+
+        // Power factor, reactive and apparent power share one block. It also holds totals and
+        // the excitation type, which are skipped.
+        if let Some(records) = self.get_optional_records(socket, &Inverter::GRID_POWER)? {
+            for record in records {
+                if record.lri == GridMsTotPF as u32 {
+                    grid_info.power_factor = record.value.as_measurement(1000);
+                } else if record.lri == AcMsVAr0 as u32 {
+                    grid_info.reactive_power.insert(1, record.value.as_measurement(1));
+                } else if record.lri == AcMsVAr1 as u32 {
+                    grid_info.reactive_power.insert(2, record.value.as_measurement(1));
+                } else if record.lri == AcMsVAr2 as u32 {
+                    grid_info.reactive_power.insert(3, record.value.as_measurement(1));
+                } else if record.lri == AcMsVA0 as u32 {
+                    grid_info.apparent_power.insert(1, record.value.as_measurement(1));
+                } else if record.lri == AcMsVA1 as u32 {
+                    grid_info.apparent_power.insert(2, record.value.as_measurement(1));
+                } else if record.lri == AcMsVA2 as u32 {
+                    grid_info.apparent_power.insert(3, record.value.as_measurement(1));
                 }
-                Ok(ac_info)
             }
-            Err(error) => Err(InverterError {
-                message: error.message,
-            }),
         }
+        Ok(grid_info)
     }
 
     pub fn get_energy_production(
         &mut self,
         socket: &Socket,
     ) -> Result<EnergyProductionInfo, InverterError> {
-        match self.get_data(socket, &Inverter::ENERGY_PRODUCTION) {
-            Ok(mut buffer) => {
-                let mut ep_info = EnergyProductionInfo {
-                    daily_wh: 0,
-                    total_wh: 0,
-                };
-
-                while buffer.len() > buffer.get_rpos() {
-                    let code = buffer.read_u32();
-                    if code == 0 {
-                        return Ok(ep_info);
-                    }
-                    let lri = code & 0x00FFFF00;
-                    let _data_type = code >> 24;
-                    if lri == MeteringTotWhOut as u32 && ep_info.total_wh == 0 {
-                        let _date = buffer.read_u32();
-                        let value = buffer.read_u32();
-                        ep_info.total_wh = value;
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    } else if lri == MeteringDyWhOut as u32 && ep_info.daily_wh == 0 {
-                        let _date = buffer.read_u32();
-                        let value = buffer.read_u32();
-                        ep_info.daily_wh = value;
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                        buffer.read_u32();
-                    } else {
-                        log!(format!("unhandled (energy production): {:x}", lri));
-                        break;
-                    }
+        let mut ep_info = EnergyProductionInfo {
+            daily_wh: None,
+            total_wh: None,
+        };
+
+        for record in self.get_records(socket, &Inverter::ENERGY_PRODUCTION)? {
+            if record.lri == MeteringTotWhOut as u32 {
+                ep_info.total_wh = record.value.as_u64();
+            } else if record.lri == MeteringDyWhOut as u32 {
+                ep_info.daily_wh = record.value.as_u64();
+            } else {
+                log!(format!("unhandled (energy production): {}", record));
+            }
+        }
+        Ok(ep_info)
+    }
+
+    pub fn get_operation_time(&mut self, socket: &Socket) -> Result<OperationTimeInfo, InverterError> {
+        let mut ot_info = OperationTimeInfo {
+            operating_time: None,
+            feed_in_time: None,
+        };
+
+        for record in self.get_records(socket, &Inverter::OPERATION_TIME)? {
+            if record.lri == MeteringTotOpTms as u32 {
+                ot_info.operating_time = record.value.as_u64();
+            } else if record.lri == MeteringTotFeedTms as u32 {
+                ot_info.feed_in_time = record.value.as_u64();
+            } else {
+                log!(format!("unhandled (operation time): {}", record));
+            }
+        }
+        Ok(ot_info)
+    }
+
+    pub fn get_battery_energy(&mut self, socket: &Socket) -> Result<BatteryEnergyInfo, InverterError> {
+        let mut be_info = BatteryEnergyInfo {
+            charged_wh: BTreeMap::new(),
+            discharged_wh: BTreeMap::new(),
+            daily_charged_wh: BTreeMap::new(),
+            daily_discharged_wh: BTreeMap::new(),
+        };
+
+        for record in self.get_records(socket, &Inverter::BATTERY_ENERGY)? {
+            if record.lri == BatChrgBatChrg as u32 {
+                be_info.charged_wh.insert(record.channel, record.value.as_u64());
+            } else if record.lri == BatDschBatDsch as u32 {
+                be_info.discharged_wh.insert(record.channel, record.value.as_u64());
+            } else {
+                log!(format!("unhandled (battery energy): {}", record));
+            }
+        }
+
+        // Older firmware only keeps the totals.
+        if let Some(records) = self.get_optional_records(socket, &Inverter::BATTERY_DAILY_ENERGY)? {
+            for record in records {
+                if record.lri == BatChrgDyBatChrg as u32 {
+                    be_info.daily_charged_wh.insert(record.channel, record.value.as_u64());
+                } else if record.lri == BatDschDyBatDsch as u32 {
+                    be_info.daily_discharged_wh.insert(record.channel, record.value.as_u64());
+                } else {
+                    log!(format!("unhandled (battery daily energy): {}", record));
                 }
-                Ok(ep_info)
             }
-            Err(error) => {
-                Err(InverterError {
-                    message: error.message,
-                })
+        }
+        Ok(be_info)
+    }
+
+    pub fn get_grid_exchange(&mut self, socket: &Socket) -> Result<GridExchangeInfo, InverterError> {
+        let mut ge_info = GridExchangeInfo {
+            export_power: None,
+            import_power: None,
+            export_wh: None,
+            import_wh: None,
+        };
+
+        for record in self.get_records(socket, &Inverter::GRID_EXCHANGE_POWER)? {
+            if record.lri == MeteringGridMsTotWOut as u32 {
+                ge_info.export_power = record.value.as_measurement(1);
+            } else if record.lri == MeteringGridMsTotWIn as u32 {
+                ge_info.import_power = record.value.as_measurement(1);
+            } else {
+                log!(format!("unhandled (grid exchange power): {}", record));
             }
         }
+
+        // Keep the power values if the device has no grid energy counters.
+        if let Some(records) = self.get_optional_records(socket, &Inverter::GRID_EXCHANGE_ENERGY)? {
+            for record in records {
+                if record.lri == MeteringGridMsTotWhOut as u32 {
+                    ge_info.export_wh = record.value.as_u64();
+                } else if record.lri == MeteringGridMsTotWhIn as u32 {
+                    ge_info.import_wh = record.value.as_u64();
+                } else {
+                    log!(format!("unhandled (grid exchange energy): {}", record));
+                }
+            }
+        }
+        Ok(ge_info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(record_size: usize, bytes: &[u8]) -> Records {
+        let mut buffer = ByteBuffer::from_bytes(bytes);
+        buffer.set_endian(LittleEndian);
+        Records { buffer, record_size }
+    }
+
+    fn record(code: u32, data: &[u32]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&code.to_le_bytes());
+        bytes.extend_from_slice(&0x65000000_u32.to_le_bytes());
+        for word in data {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn counter_records() {
+        let mut bytes = record(0x00260101, &[0xBE991A14, 0x1C]);
+        bytes.extend(record(0x00262201, &[0x00000000, 0x80000000]));
+        let mut records = records(16, &bytes);
+
+        let total = records.next().unwrap();
+        assert_eq!(total.code, 0x00260101);
+        assert_eq!(total.lri, MeteringTotWhOut as u32);
+        assert_eq!(total.channel, 1);
+        assert_eq!(total.data_type, 0x00);
+        assert_eq!(total.date, 0x65000000);
+        assert_eq!(total.value.as_u64(), Some(0x1CBE991A14));
+
+        let daily = records.next().unwrap();
+        assert_eq!(daily.lri, MeteringDyWhOut as u32);
+        assert_eq!(daily.value.as_u64(), None);
+        assert!(records.next().is_none());
+    }
+
+    #[test]
+    fn numeric_records() {
+        let mut bytes = record(0x40251E01, &[(-5_i32) as u32, 0, 0, 0, 1]);
+        bytes.extend(record(0x40251E02, &[NAN_S32, NAN_S32, NAN_S32, NAN_S32, 1]));
+        bytes.extend(record(0x00295A01, &[NAN_U32, NAN_U32, NAN_U32, NAN_U32, 1]));
+        bytes.extend(record(0x40464001, &[NAN_U32, NAN_U32, NAN_U32, NAN_U32, 1]));
+        bytes.extend(record(0x00295A02, &[87, 0, 100, 87, 1]));
+        // End marker of the packet, shorter than a record.
+        bytes.extend(0_u32.to_le_bytes());
+        let mut records = records(28, &bytes);
+
+        let power = records.next().unwrap();
+        assert_eq!(power.code, 0x40251E01);
+        assert_eq!(power.lri, DcMsWatt as u32);
+        assert_eq!(power.data_type, DT_SLONG);
+        assert_eq!(power.to_string(), "40251e01 (type 40) at 1694498816");
+        assert!(matches!(power.value, RecordValue::I32(Some(-5))));
+        assert!(matches!(records.next().unwrap().value, RecordValue::I32(None)));
+        assert_eq!(records.next().unwrap().value.as_u32(), None);
+        // 0xFFFFFFFF is a valid reading of -1 in signed records.
+        let power = records.next().unwrap();
+        assert!(matches!(power.value, RecordValue::I32(Some(-1))));
+        assert_eq!(power.value.as_measurement(1).map(|value| value.value()), Some(-1_f64));
+
+        let charge = records.next().unwrap();
+        assert_eq!(charge.lri, BatChaStt as u32);
+        assert_eq!(charge.channel, 2);
+        assert_eq!(charge.value.as_measurement(1).map(|value| value.value()), Some(87_f64));
+        assert!(records.next().is_none());
+    }
+
+    #[test]
+    fn status_records() {
+        let ok = Tag::Ok as u32;
+        let fault = Tag::Fault as u32;
+        let warning = Tag::Warning as u32;
+        let mut bytes = record(0x08214801, &[fault, ok | 0x01000000, warning, 0x00FFFFFE, 0, 0, 0, 0]);
+        // A flag behind the end of the list is not a selected tag.
+        bytes.extend(record(0x08214802, &[fault, 0x00FFFFFE, ok | 0x01000000, 0, 0, 0, 0, 0]));
+        let mut records = records(40, &bytes);
+
+        let status = records.next().unwrap();
+        assert_eq!(status.lri, OperationHealth as u32);
+        assert_eq!(status.data_type, DT_STATUS);
+        assert_eq!(status.value.as_tag(), Some(ok));
+        assert_eq!(records.next().unwrap().value.as_tag(), None);
+        assert!(records.next().is_none());
+    }
+
+    #[test]
+    fn string_and_long_records() {
+        let mut name = [0_u8; 32];
+        name[0..8].copy_from_slice(b"SB 5.0 \0");
+        let mut bytes = 0x10821E01_u32.to_le_bytes().to_vec();
+        bytes.extend(0_u32.to_le_bytes());
+        bytes.extend(name);
+        bytes.extend(record(0x00823401, &[0, 0, 0, 0, 0x02100C04, 0, 0, 0]));
+        bytes.extend(record(0, &[0, 0, 0, 0, 0, 0, 0, 0]));
+        bytes.extend(record(0x00823401, &[0, 0, 0, 0, 0x02100C04, 0, 0, 0]));
+        let mut records = records(40, &bytes);
+
+        let device_name = records.next().unwrap();
+        assert_eq!(device_name.lri, NameplateLocation as u32);
+        assert_eq!(device_name.data_type, DT_STRING);
+        assert_eq!(device_name.date, 0);
+        assert!(matches!(device_name.value, RecordValue::String(ref name) if name == "SB 5.0"));
+
+        let firmware = records.next().unwrap();
+        assert_eq!(firmware.lri, NameplatePkgRev as u32);
+        assert_eq!(firmware.value.as_u32(), Some(0x02100C04));
+        // A zero code ends the records.
+        assert!(records.next().is_none());
+    }
+
+    fn fragment(fragment_id: u16, error_code: u16, codes: &[u32]) -> Response {
+        let mut bytes = vec![0_u8; 12];
+        for code in codes {
+            bytes.extend(record(*code, &[1, 0, 0, 0, 1]));
+        }
+        // End marker of the packet.
+        bytes.extend(0_u32.to_le_bytes());
+        let mut buffer = ByteBuffer::from_bytes(&bytes);
+        buffer.set_endian(LittleEndian);
+        Response {
+            buffer,
+            source_susy_id: 0,
+            source_serial: 0,
+            error_code,
+            fragment_id,
+        }
+    }
+
+    fn reassembled(buffer: ByteBuffer) -> Vec<u32> {
+        Records { buffer, record_size: 28 }.map(|record| record.code).collect()
+    }
+
+    #[test]
+    fn fragments_are_reassembled() {
+        let data_type = Inverter::SPOT_DC_VOLTAGE;
+        let mut records = ByteBuffer::new();
+        records.set_endian(LittleEndian);
+
+        let next = Inverter::append_fragment(&mut records, fragment(2, 0, &[0x40251E01, 0x40251E02]), None, &data_type);
+        assert!(matches!(next, Ok(Some(1))));
+        let next = Inverter::append_fragment(&mut records, fragment(1, 0, &[0x40451F01]), Some(1), &data_type);
+        assert!(matches!(next, Ok(Some(0))));
+        let next = Inverter::append_fragment(&mut records, fragment(0, 0, &[0x40452101]), Some(0), &data_type);
+        assert!(matches!(next, Ok(None)));
+
+        assert_eq!(reassembled(records), vec![0x40251E01, 0x40251E02, 0x40451F01, 0x40452101]);
+    }
+
+    #[test]
+    fn single_fragment() {
+        let mut records = ByteBuffer::new();
+        records.set_endian(LittleEndian);
+        let next = Inverter::append_fragment(&mut records, fragment(0, 0, &[0x40251E01]), None, &Inverter::SPOT_DC_VOLTAGE);
+        assert!(matches!(next, Ok(None)));
+        assert_eq!(reassembled(records), vec![0x40251E01]);
+    }
+
+    #[test]
+    fn fragment_gaps_and_repeats() {
+        let data_type = Inverter::SPOT_DC_VOLTAGE;
+        let mut records = ByteBuffer::new();
+
+        let gap = Inverter::append_fragment(&mut records, fragment(0, 0, &[0x40251E01]), Some(1), &data_type);
+        assert!(matches!(gap, Err(InverterError::Truncated)));
+        let repeat = Inverter::append_fragment(&mut records, fragment(2, 0, &[0x40251E01]), Some(1), &data_type);
+        assert!(matches!(repeat, Err(InverterError::Truncated)));
+        let unsupported = Inverter::append_fragment(&mut records, fragment(0, 21, &[]), None, &data_type);
+        assert!(matches!(unsupported, Err(InverterError::Unsupported(0x00251E00))));
+        assert_eq!(records.len(), 0);
+    }
+
+    #[test]
+    fn device_classes() {
+        let solar = DeviceClass::new(9300, Some(Tag::SolarInverters as u32));
+        assert!(matches!(solar, DeviceClass::SolarInverter));
+        assert!(solar.is_inverter());
+        assert!(matches!(DeviceClass::new(9300, Some(Tag::BatteryInverters as u32)), DeviceClass::BatteryInverter));
+        // Hybrid inverters and devices without a class are polled.
+        assert!(DeviceClass::new(9300, Some(8009)).is_inverter());
+        assert!(DeviceClass::new(9300, None).is_inverter());
+
+        let meter = DeviceClass::new(9300, Some(Tag::ElectricityMeter as u32));
+        assert!(matches!(meter, DeviceClass::EnergyMeter));
+        assert!(!meter.is_inverter());
+        assert!(!DeviceClass::new(349, None).is_inverter());
+        let home_manager = DeviceClass::new(372, Some(Tag::CommunicationProducts as u32));
+        assert!(matches!(home_manager, DeviceClass::HomeManager));
+        assert!(!home_manager.is_inverter());
+        assert!(!DeviceClass::new(9300, Some(Tag::CommunicationProducts as u32)).is_inverter());
+    }
+
+    #[test]
+    fn firmware_version() {
+        assert_eq!(Inverter::read_firmware_version(0x02100C04), "02.10.12.R");
+        assert_eq!(Inverter::read_firmware_version(0x03221709), "03.22.23.9");
     }
 }
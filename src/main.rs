@@ -1,7 +1,7 @@
 extern crate config;
 
-use crate::inverter::Inverter;
-use crate::udp_client::initialize_socket;
+use crate::inverter::{DeviceClass, DiscoveredDevice, Inverter, InverterError, Tag, UserGroup};
+use crate::udp_client::{initialize_socket, interface_address};
 use config::{Config, File};
 use http_body_util::{combinators::BoxBody, BodyExt, Full};
 use hyper::body::Bytes;
@@ -10,14 +10,14 @@ use hyper::service::service_fn;
 use hyper::{Request, Response};
 use hyper_util::rt::TokioIo;
 use lazy_static::lazy_static;
-use prometheus::{gather, register, Encoder, GaugeVec, Opts, TextEncoder};
-use socket2::SockAddr;
+use prometheus::{gather, register, CounterVec, Encoder, GaugeVec, Opts, TextEncoder};
+use socket2::{SockAddr, Socket};
 use std::borrow::Borrow;
 use std::collections::HashMap;
 use std::convert::Infallible;
 use std::io::{Error, Write};
 use std::mem::MaybeUninit;
-use std::net::{Ipv4Addr, Shutdown, SocketAddr};
+use std::net::{Ipv4Addr, Shutdown, SocketAddr, ToSocketAddrs};
 use std::process::exit;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
@@ -54,13 +54,103 @@ const BAT_VOLTAGE: &str = "smainverter_battery_voltage_millivolts";
 const BAT_CURRENT: &str = "smainverter_battery_current_milliamperes";
 const BAT_CHARGE: &str = "smainverter_battery_charge_percentage";
 const BAT_TEMPERATURE: &str = "smainverter_battery_temperature_degreescelsius";
+const BAT_STATE_OF_HEALTH: &str = "smainverter_battery_state_of_health_percent";
+const BAT_RATED_CAPACITY: &str = "smainverter_battery_rated_capacity_watthours";
+const BAT_CHARGE_CYCLES: &str = "smainverter_battery_charge_cycles_total";
+const BAT_CHARGED_AH: &str = "smainverter_battery_charged_amperehours_total";
+const BAT_DISCHARGED_AH: &str = "smainverter_battery_discharged_amperehours_total";
+const BAT_CHARGED_ENERGY: &str = "smainverter_battery_charged_watthours_total";
+const BAT_DISCHARGED_ENERGY: &str = "smainverter_battery_discharged_watthours_total";
+const BAT_DAILY_CHARGED_ENERGY: &str = "smainverter_battery_daily_charged_watthours";
+const BAT_DAILY_DISCHARGED_ENERGY: &str = "smainverter_battery_daily_discharged_watthours";
+const BAT_POWER: &str = "smainverter_battery_power_watts";
+const BAT_OPERATING_STATE: &str = "smainverter_battery_operating_state";
 const DC_VOLTAGE: &str = "smainverter_spot_dc_voltage_millivolts";
 const DC_CURRENT: &str = "smainverter_spot_dc_current_milliamperes";
+const DC_POWER: &str = "smainverter_spot_dc_power_watts";
 const AC_VOLTAGE: &str = "smainverter_spot_ac_voltage_millivolts";
 const AC_CURRENT: &str = "smainverter_spot_ac_current_milliamperes";
+const AC_REACTIVE_POWER: &str = "smainverter_spot_ac_reactive_power_var";
+const AC_APPARENT_POWER: &str = "smainverter_spot_ac_apparent_power_va";
+const GRID_FREQUENCY: &str = "smainverter_spot_grid_frequency_hertz";
+const GRID_POWER_FACTOR: &str = "smainverter_spot_grid_power_factor";
+const AC_POWER: &str = "smainverter_spot_ac_power_watts";
+const AC_TOTAL_POWER: &str = "smainverter_spot_ac_total_power_watts";
+const GRID_EXPORT_POWER: &str = "smainverter_grid_export_power_watts";
+const GRID_IMPORT_POWER: &str = "smainverter_grid_import_power_watts";
+const GRID_EXPORT_ENERGY: &str = "smainverter_grid_export_watthours_total";
+const GRID_IMPORT_ENERGY: &str = "smainverter_grid_import_watthours_total";
+const DEVICE_INFO: &str = "smainverter_device_info";
+const DEVICE_STATUS: &str = "smainverter_device_status";
+const GRID_RELAY_STATUS: &str = "smainverter_grid_relay_status";
+const INVERTER_TEMPERATURE: &str = "smainverter_internal_temperature_celsius";
+const OPERATING_TIME: &str = "smainverter_operating_time_seconds_total";
+const FEED_IN_TIME: &str = "smainverter_feed_in_time_seconds_total";
 const PRODUCTION_TOTAL: &str = "smainverter_metering_total_watthours";
 const PRODUCTION_DAILY: &str = "smainverter_metering_daily_watthours";
 
+/// Batteries have always been labelled A, B, C, ... so channel 1 maps to A.
+fn battery_label(channel: u8) -> String {
+    if (1..=26).contains(&channel) {
+        ((b'A' + channel - 1) as char).to_string()
+    } else {
+        channel.to_string()
+    }
+}
+
+const DEVICE_STATUS_TAGS: [(Tag, &str); 4] = [
+    (Tag::Ok, "ok"),
+    (Tag::Warning, "warning"),
+    (Tag::Fault, "fault"),
+    (Tag::Off, "off"),
+];
+
+const GRID_RELAY_STATUS_TAGS: [(Tag, &str); 2] = [
+    (Tag::Closed, "closed"),
+    (Tag::Open, "open"),
+];
+
+const BATTERY_OPERATING_STATE_TAGS: [(Tag, &str); 4] = [
+    (Tag::Standby, "standby"),
+    (Tag::Charge, "charge"),
+    (Tag::Discharge, "discharge"),
+    (Tag::Off, "off"),
+];
+
+/// What to export for values the inverter reports as not available, e.g. DC values at night.
+#[derive(Clone, Copy)]
+enum NotAvailable {
+    Nan,
+    Remove,
+}
+
+fn set_gauge(gauge: &GaugeVec, labels: &[&str], value: Option<f64>, not_available: NotAvailable) {
+    match (value, not_available) {
+        (Some(value), _) => gauge.with_label_values(labels).set(value),
+        (None, NotAvailable::Nan) => gauge.with_label_values(labels).set(f64::NAN),
+        (None, NotAvailable::Remove) => {
+            let _ = gauge.remove_label_values(labels);
+        }
+    }
+}
+
+/// The inverter keeps the counter, so replace the exported value with the reported one.
+/// Callers must hold `LOCK` so no scrape sees the counter in between.
+/// Counters cannot be NaN, so they keep their last value unless series are removed.
+fn set_counter(counter: &CounterVec, labels: &[&str], value: Option<f64>, not_available: NotAvailable) {
+    match (value, not_available) {
+        (Some(value), _) => {
+            let counter = counter.with_label_values(labels);
+            counter.reset();
+            counter.inc_by(value);
+        }
+        (None, NotAvailable::Nan) => {}
+        (None, NotAvailable::Remove) => {
+            let _ = counter.remove_label_values(labels);
+        }
+    }
+}
+
 fn is_discovery_response(response_packet: &[u8]) -> bool {
     // Discovery response packet as per https://cdn.sma.de/fileadmin/content/www.developer.sma.de/docs/SpeedwireDD-TI-en-10.pdf?v=1699275967
     let discovery_response = [
@@ -72,8 +162,33 @@ fn is_discovery_response(response_packet: &[u8]) -> bool {
     response_packet.starts_with(&discovery_response)
 }
 
-fn find_inverters() -> Result<Vec<Inverter>, Error> {
-    let mut socket = initialize_socket(true);
+/// Runs discovery on every interface, devices reachable through several of them are kept once.
+fn find_inverters(interfaces: &[Ipv4Addr]) -> Result<Vec<DiscoveredDevice>, Error> {
+    let mut devices: Vec<DiscoveredDevice> = Vec::new();
+    let mut last_error = None;
+    for interface in interfaces {
+        match find_inverters_on(*interface) {
+            Ok(found_devices) => {
+                for device in found_devices {
+                    if !devices.iter().any(|d| d.address == device.address && d.serial == device.serial) {
+                        devices.push(device);
+                    }
+                }
+            }
+            Err(err) => {
+                log!(format!("Discovery on interface {} failed. {}", interface, err));
+                last_error = Some(err);
+            }
+        }
+    }
+    match last_error {
+        Some(err) if devices.is_empty() => Err(err),
+        _ => Ok(devices),
+    }
+}
+
+fn find_inverters_on(interface: Ipv4Addr) -> Result<Vec<DiscoveredDevice>, Error> {
+    let mut socket = initialize_socket(true, interface)?;
     match socket.send_to(
         [
             0x53, 0x4D, 0x41, 0x00, 0x00, 0x04, 0x02, 0xA0, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00,
@@ -101,7 +216,7 @@ fn find_inverters() -> Result<Vec<Inverter>, Error> {
         }
     }
 
-    let mut inverters = Vec::new();
+    let mut addresses: Vec<SocketAddr> = Vec::new();
     let mut buf = [MaybeUninit::new(0_u8); 18];
     match socket.set_read_timeout(Some(Duration::from_millis(100))) {
         Ok(_x) => {}
@@ -117,9 +232,9 @@ fn find_inverters() -> Result<Vec<Inverter>, Error> {
             Ok((len, remote_addr)) => {
                 if len == 18 {
                     let ibuf = unsafe { mem::transmute::<[MaybeUninit<u8>; 18], [u8; 18]>(buf) };
-                    if is_discovery_response(&ibuf) {
-                        println!("found {}", remote_addr.as_socket().unwrap());
-                        inverters.push(Inverter::new(remote_addr.as_socket().unwrap()))
+                    let address = remote_addr.as_socket().unwrap();
+                    if is_discovery_response(&ibuf) && !addresses.contains(&address) {
+                        addresses.push(address);
                     }
                 }
             }
@@ -134,7 +249,446 @@ fn find_inverters() -> Result<Vec<Inverter>, Error> {
             log!(format!("Unable to shutdown socket. {}", error));
         }
     }
-    Ok(inverters)
+
+    // Ask every responder who it is, unicast so the answers are not mixed with multicast traffic.
+    let mut devices = Vec::new();
+    let socket = initialize_socket(false, interface)?;
+    for address in addresses {
+        match Inverter::new(address).identify(&socket) {
+            Ok(device) => {
+                log!(format!("Found {} {} with serial {} and SUSy id {}",
+                    device.device_class, device.address, device.serial, device.susy_id));
+                devices.push(device);
+            }
+            Err(inverter_error) => {
+                // Still try to log in, the class is checked again after login.
+                log!(format!("Unable to identify device {}. {}", address, inverter_error));
+                devices.push(DiscoveredDevice {
+                    address,
+                    susy_id: 0,
+                    serial: 0,
+                    device_class: DeviceClass::Other(None),
+                });
+            }
+        }
+    }
+    match socket.shutdown(Shutdown::Both) {
+        Ok(_result) => {}
+        Err(error) => {
+            log!(format!("Unable to shutdown socket. {}", error));
+        }
+    }
+    Ok(devices)
+}
+
+/// Per inverter settings are looked up by serial if it is known and then by IP address.
+fn inverter_setting(settings: &Config, i: &Inverter, name: &str) -> Option<String> {
+    if i.device_serial != 0
+        && let Ok(value) = settings.get_string(format!("{}.{}", i.device_serial, name).as_str())
+    {
+        return Some(value);
+    }
+    settings.get_string(format!("{}.{}", i.address.ip(), name).as_str()).ok()
+}
+
+/// Logs in again unless waiting for the next attempt, true if the inverter has a session.
+fn relogin(i: &mut Inverter, socket: &Socket) -> bool {
+    match i.relogin(socket) {
+        Some(Ok(_result)) => {
+            log!(format!("Inverter {} logged in again.", i.address));
+            true
+        }
+        Some(Err(inverter_error)) => {
+            log!(format!("Inverter {} error: {}", i.address, inverter_error));
+            false
+        }
+        None => false,
+    }
+}
+
+/// What a poll step needs besides the inverter.
+struct PollContext<'a> {
+    socket: &'a Socket,
+    gauges: &'a HashMap<&'static str, GaugeVec>,
+    counters: &'a HashMap<&'static str, CounterVec>,
+    not_available: NotAvailable,
+    serial: String,
+    susy_id: String,
+    /// Last DEVICE_INFO label values by serial, so a changed name or firmware replaces the old
+    /// series instead of adding one.
+    device_info: &'a mut HashMap<String, Vec<String>>,
+}
+
+/// Queries one group of values and exports them, named for the log message on failure.
+type PollStep = (&'static str, fn(&mut Inverter, &mut PollContext) -> Result<(), InverterError>);
+
+const POLL_STEPS: [PollStep; 14] = [
+    ("device info", poll_device_info),
+    ("battery info", poll_battery_info),
+    ("battery state", poll_battery_state),
+    ("DC voltage", poll_dc_voltage),
+    ("AC voltage", poll_ac_voltage),
+    ("AC power", poll_ac_power),
+    ("grid info", poll_grid_info),
+    ("grid exchange", poll_grid_exchange),
+    ("device status", poll_device_status),
+    ("temperature", poll_inverter_temperature),
+    ("battery charge", poll_battery_charge_status),
+    ("energy production", poll_energy_production),
+    ("operation time", poll_operation_time),
+    ("battery energy", poll_battery_energy),
+];
+
+fn poll_inverter(i: &mut Inverter, context: &mut PollContext) {
+    for (name, step) in POLL_STEPS {
+        // Stop polling an inverter that lost its session, so the others are not held up.
+        if i.needs_login() {
+            break;
+        }
+        match step(i, context) {
+            Ok(()) | Err(InverterError::Unsupported(_)) => {}
+            Err(inverter_error) => {
+                log!(format!("[{}] Unable to get {} from inverter. {}",
+                    &i.address.ip().to_string(), name, inverter_error));
+            }
+        }
+    }
+}
+
+fn poll_device_info(i: &mut Inverter, context: &mut PollContext) -> Result<(), InverterError> {
+    let data = i.get_device_info(context.socket)?;
+    let _lock = LOCK.lock().unwrap();
+    let model = match data.model {
+        Some(model) => model.to_string(),
+        None => String::new(),
+    };
+    let labels = vec![
+        context.serial.clone(),
+        context.susy_id.clone(),
+        i.address.ip().to_string(),
+        model,
+        data.name,
+        data.firmware,
+    ];
+    let gauge = context.gauges.get(DEVICE_INFO).unwrap();
+    if let Some(old_labels) = context.device_info.get(&context.serial)
+        && *old_labels != labels
+    {
+        let old_labels: Vec<&str> = old_labels.iter().map(String::as_str).collect();
+        let _ = gauge.remove_label_values(&old_labels);
+    }
+    let new_labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+    gauge.with_label_values(&new_labels).set(1_f64);
+    context.device_info.insert(context.serial.clone(), labels);
+    Ok(())
+}
+
+fn poll_battery_info(i: &mut Inverter, context: &mut PollContext) -> Result<(), InverterError> {
+    let data = i.get_battery_info(context.socket)?;
+    let _lock = LOCK.lock().unwrap();
+    for (channel, value) in &data.temperature {
+        set_gauge(context.gauges.get(BAT_TEMPERATURE).unwrap(),
+            &[&context.serial, &context.susy_id, &battery_label(*channel)],
+            value.map(|value| value.value()), context.not_available);
+    }
+    for (channel, value) in &data.voltage {
+        set_gauge(context.gauges.get(BAT_VOLTAGE).unwrap(),
+            &[&context.serial, &context.susy_id, &battery_label(*channel)],
+            value.map(|value| value.milli()), context.not_available);
+    }
+    for (channel, value) in &data.current {
+        set_gauge(context.gauges.get(BAT_CURRENT).unwrap(),
+            &[&context.serial, &context.susy_id, &battery_label(*channel)],
+            value.map(|value| value.milli()), context.not_available);
+    }
+    for (channel, value) in &data.state_of_health {
+        set_gauge(context.gauges.get(BAT_STATE_OF_HEALTH).unwrap(),
+            &[&context.serial, &context.susy_id, &battery_label(*channel)],
+            value.map(|value| value.value()), context.not_available);
+    }
+    for (channel, value) in &data.rated_capacity {
+        set_gauge(context.gauges.get(BAT_RATED_CAPACITY).unwrap(),
+            &[&context.serial, &context.susy_id, &battery_label(*channel)],
+            value.map(|value| value.value()), context.not_available);
+    }
+    for (channel, value) in &data.charge_cycles {
+        set_counter(context.counters.get(BAT_CHARGE_CYCLES).unwrap(),
+            &[&context.serial, &context.susy_id, &battery_label(*channel)],
+            value.map(|value| value as f64), context.not_available);
+    }
+    for (channel, value) in &data.charged_ah {
+        set_counter(context.counters.get(BAT_CHARGED_AH).unwrap(),
+            &[&context.serial, &context.susy_id, &battery_label(*channel)],
+            value.map(|value| value as f64), context.not_available);
+    }
+    for (channel, value) in &data.discharged_ah {
+        set_counter(context.counters.get(BAT_DISCHARGED_AH).unwrap(),
+            &[&context.serial, &context.susy_id, &battery_label(*channel)],
+            value.map(|value| value as f64), context.not_available);
+    }
+    Ok(())
+}
+
+fn poll_battery_state(i: &mut Inverter, context: &mut PollContext) -> Result<(), InverterError> {
+    let data = i.get_battery_state(context.socket)?;
+    let _lock = LOCK.lock().unwrap();
+    for (channel, operating_state) in &data.operating_state {
+        for (tag, state) in BATTERY_OPERATING_STATE_TAGS {
+            context
+                .gauges
+                .get(BAT_OPERATING_STATE)
+                .unwrap()
+                .with_label_values(&[&context.serial, &context.susy_id, &battery_label(*channel), state])
+                .set(if *operating_state == tag as u32 { 1_f64 } else { 0_f64 });
+        }
+    }
+    for (channel, value) in &data.charge_power {
+        set_gauge(context.gauges.get(BAT_POWER).unwrap(),
+            &[&context.serial, &context.susy_id, &battery_label(*channel), "charge"],
+            value.map(|value| value.value()), context.not_available);
+    }
+    for (channel, value) in &data.discharge_power {
+        set_gauge(context.gauges.get(BAT_POWER).unwrap(),
+            &[&context.serial, &context.susy_id, &battery_label(*channel), "discharge"],
+            value.map(|value| value.value()), context.not_available);
+    }
+    Ok(())
+}
+
+fn poll_dc_voltage(i: &mut Inverter, context: &mut PollContext) -> Result<(), InverterError> {
+    let data = i.get_dc_voltage(context.socket)?;
+    let _lock = LOCK.lock().unwrap();
+    for (channel, value) in &data.current {
+        set_gauge(context.gauges.get(DC_CURRENT).unwrap(),
+            &[&context.serial, &context.susy_id, &channel.to_string()],
+            value.map(|value| value.milli()), context.not_available);
+    }
+    for (channel, value) in &data.voltage {
+        set_gauge(context.gauges.get(DC_VOLTAGE).unwrap(),
+            &[&context.serial, &context.susy_id, &channel.to_string()],
+            value.map(|value| value.milli()), context.not_available);
+    }
+    for (channel, value) in &data.power {
+        set_gauge(context.gauges.get(DC_POWER).unwrap(),
+            &[&context.serial, &context.susy_id, &channel.to_string()],
+            value.map(|value| value.value()), context.not_available);
+    }
+    Ok(())
+}
+
+fn poll_ac_voltage(i: &mut Inverter, context: &mut PollContext) -> Result<(), InverterError> {
+    let data = i.get_ac_voltage(context.socket)?;
+    let _lock = LOCK.lock().unwrap();
+    for (phase, value) in &data.current {
+        set_gauge(context.gauges.get(AC_CURRENT).unwrap(),
+            &[&context.serial, &context.susy_id, &phase.to_string()],
+            value.map(|value| value.milli()), context.not_available);
+    }
+    for (phase, value) in &data.voltage {
+        set_gauge(context.gauges.get(AC_VOLTAGE).unwrap(),
+            &[&context.serial, &context.susy_id, &phase.to_string()],
+            value.map(|value| value.milli()), context.not_available);
+    }
+    Ok(())
+}
+
+fn poll_ac_power(i: &mut Inverter, context: &mut PollContext) -> Result<(), InverterError> {
+    let data = i.get_ac_power(context.socket)?;
+    let _lock = LOCK.lock().unwrap();
+    for (phase, value) in &data.power {
+        set_gauge(context.gauges.get(AC_POWER).unwrap(),
+            &[&context.serial, &context.susy_id, &phase.to_string()],
+            value.map(|value| value.value()), context.not_available);
+    }
+    set_gauge(context.gauges.get(AC_TOTAL_POWER).unwrap(), &[&context.serial, &context.susy_id],
+        data.total.map(|value| value.value()), context.not_available);
+    Ok(())
+}
+
+fn poll_grid_info(i: &mut Inverter, context: &mut PollContext) -> Result<(), InverterError> {
+    let data = i.get_grid_info(context.socket)?;
+    let _lock = LOCK.lock().unwrap();
+    set_gauge(context.gauges.get(GRID_FREQUENCY).unwrap(), &[&context.serial, &context.susy_id],
+        data.frequency.map(|value| value.value()), context.not_available);
+    set_gauge(context.gauges.get(GRID_POWER_FACTOR).unwrap(), &[&context.serial, &context.susy_id],
+        data.power_factor.map(|value| value.value()), context.not_available);
+    for (phase, value) in &data.reactive_power {
+        set_gauge(context.gauges.get(AC_REACTIVE_POWER).unwrap(),
+            &[&context.serial, &context.susy_id, &phase.to_string()],
+            value.map(|value| value.value()), context.not_available);
+    }
+    for (phase, value) in &data.apparent_power {
+        set_gauge(context.gauges.get(AC_APPARENT_POWER).unwrap(),
+            &[&context.serial, &context.susy_id, &phase.to_string()],
+            value.map(|value| value.value()), context.not_available);
+    }
+    Ok(())
+}
+
+fn poll_grid_exchange(i: &mut Inverter, context: &mut PollContext) -> Result<(), InverterError> {
+    let data = i.get_grid_exchange(context.socket)?;
+    let _lock = LOCK.lock().unwrap();
+    set_gauge(context.gauges.get(GRID_EXPORT_POWER).unwrap(), &[&context.serial, &context.susy_id],
+        data.export_power.map(|value| value.value()), context.not_available);
+    set_gauge(context.gauges.get(GRID_IMPORT_POWER).unwrap(), &[&context.serial, &context.susy_id],
+        data.import_power.map(|value| value.value()), context.not_available);
+    set_counter(context.counters.get(GRID_EXPORT_ENERGY).unwrap(), &[&context.serial, &context.susy_id],
+        data.export_wh.map(|value| value as f64), context.not_available);
+    set_counter(context.counters.get(GRID_IMPORT_ENERGY).unwrap(), &[&context.serial, &context.susy_id],
+        data.import_wh.map(|value| value as f64), context.not_available);
+    Ok(())
+}
+
+fn poll_device_status(i: &mut Inverter, context: &mut PollContext) -> Result<(), InverterError> {
+    let data = i.get_device_status(context.socket)?;
+    let _lock = LOCK.lock().unwrap();
+    if let Some(device_status) = data.device_status {
+        for (tag, status) in DEVICE_STATUS_TAGS {
+            context
+                .gauges
+                .get(DEVICE_STATUS)
+                .unwrap()
+                .with_label_values(&[&context.serial, &context.susy_id, status])
+                .set(if device_status == tag as u32 { 1_f64 } else { 0_f64 });
+        }
+    }
+    if let Some(grid_relay) = data.grid_relay {
+        for (tag, status) in GRID_RELAY_STATUS_TAGS {
+            context
+                .gauges
+                .get(GRID_RELAY_STATUS)
+                .unwrap()
+                .with_label_values(&[&context.serial, &context.susy_id, status])
+                .set(if grid_relay == tag as u32 { 1_f64 } else { 0_f64 });
+        }
+    }
+    Ok(())
+}
+
+fn poll_inverter_temperature(i: &mut Inverter, context: &mut PollContext) -> Result<(), InverterError> {
+    let data = i.get_inverter_temperature(context.socket)?;
+    let _lock = LOCK.lock().unwrap();
+    set_gauge(context.gauges.get(INVERTER_TEMPERATURE).unwrap(), &[&context.serial, &context.susy_id],
+        data.map(|value| value.value()), context.not_available);
+    Ok(())
+}
+
+fn poll_battery_charge_status(i: &mut Inverter, context: &mut PollContext) -> Result<(), InverterError> {
+    let data = i.get_battery_charge_status(context.socket)?;
+    let _lock = LOCK.lock().unwrap();
+    for (channel, value) in &data {
+        set_gauge(context.gauges.get(BAT_CHARGE).unwrap(),
+            &[&context.serial, &context.susy_id, &battery_label(*channel)],
+            value.map(|value| value.value()), context.not_available);
+    }
+    Ok(())
+}
+
+fn poll_energy_production(i: &mut Inverter, context: &mut PollContext) -> Result<(), InverterError> {
+    let data = i.get_energy_production(context.socket)?;
+    let _lock = LOCK.lock().unwrap();
+    set_gauge(context.gauges.get(PRODUCTION_DAILY).unwrap(), &[&context.serial, &context.susy_id],
+        data.daily_wh.map(|value| value as f64), context.not_available);
+    set_gauge(context.gauges.get(PRODUCTION_TOTAL).unwrap(), &[&context.serial, &context.susy_id],
+        data.total_wh.map(|value| value as f64), context.not_available);
+    Ok(())
+}
+
+fn poll_operation_time(i: &mut Inverter, context: &mut PollContext) -> Result<(), InverterError> {
+    let data = i.get_operation_time(context.socket)?;
+    let _lock = LOCK.lock().unwrap();
+    set_counter(context.counters.get(OPERATING_TIME).unwrap(), &[&context.serial, &context.susy_id],
+        data.operating_time.map(|value| value as f64), context.not_available);
+    set_counter(context.counters.get(FEED_IN_TIME).unwrap(), &[&context.serial, &context.susy_id],
+        data.feed_in_time.map(|value| value as f64), context.not_available);
+    Ok(())
+}
+
+fn poll_battery_energy(i: &mut Inverter, context: &mut PollContext) -> Result<(), InverterError> {
+    let data = i.get_battery_energy(context.socket)?;
+    let _lock = LOCK.lock().unwrap();
+    for (channel, value) in &data.charged_wh {
+        set_counter(context.counters.get(BAT_CHARGED_ENERGY).unwrap(),
+            &[&context.serial, &context.susy_id, &battery_label(*channel)],
+            value.map(|value| value as f64), context.not_available);
+    }
+    for (channel, value) in &data.discharged_wh {
+        set_counter(context.counters.get(BAT_DISCHARGED_ENERGY).unwrap(),
+            &[&context.serial, &context.susy_id, &battery_label(*channel)],
+            value.map(|value| value as f64), context.not_available);
+    }
+    for (channel, value) in &data.daily_charged_wh {
+        set_gauge(context.gauges.get(BAT_DAILY_CHARGED_ENERGY).unwrap(),
+            &[&context.serial, &context.susy_id, &battery_label(*channel)],
+            value.map(|value| value as f64), context.not_available);
+    }
+    for (channel, value) in &data.daily_discharged_wh {
+        set_gauge(context.gauges.get(BAT_DAILY_DISCHARGED_ENERGY).unwrap(),
+            &[&context.serial, &context.susy_id, &battery_label(*channel)],
+            value.map(|value| value as f64), context.not_available);
+    }
+    Ok(())
+}
+
+/// Interfaces for discovery from `interfaces=<address or name>,...`, all interfaces if not set.
+fn discovery_interfaces(settings: &Config) -> Vec<Ipv4Addr> {
+    let mut interfaces = Vec::new();
+    if let Ok(entries) = settings.get_string("interfaces") {
+        for entry in entries.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            match entry.parse::<Ipv4Addr>().ok().or_else(|| interface_address(entry)) {
+                Some(address) => interfaces.push(address),
+                None => {
+                    log!(format!("No IPv4 address for interface {}", entry));
+                }
+            }
+        }
+    }
+    if interfaces.is_empty() {
+        interfaces.push(Ipv4Addr::UNSPECIFIED);
+    }
+    interfaces
+}
+
+/// Inverters listed in the config as `inverters=<host>[@<serial>],...` for networks that multicast
+/// discovery does not reach. The serial selects one device behind a gateway.
+fn static_inverters(settings: &Config) -> Vec<Inverter> {
+    let mut inverters = Vec::new();
+    let entries = match settings.get_string("inverters") {
+        Ok(entries) => entries,
+        Err(_) => return inverters,
+    };
+    for entry in entries.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+        let (host, serial) = match entry.split_once('@') {
+            Some((host, serial)) => match serial.trim().parse::<u32>() {
+                Ok(serial) => (host.trim(), Some(serial)),
+                Err(error) => {
+                    log!(format!("Invalid serial in inverter entry {}: {}", entry, error));
+                    continue;
+                }
+            },
+            None => (entry, None),
+        };
+        let address = match (host, 9522).to_socket_addrs() {
+            Ok(mut addresses) => addresses.find(SocketAddr::is_ipv4),
+            Err(error) => {
+                log!(format!("Unable to resolve inverter {}: {}", host, error));
+                continue;
+            }
+        };
+        match address {
+            Some(address) => {
+                let mut inverter = Inverter::new(address);
+                if let Some(serial) = serial {
+                    inverter.device_serial = serial;
+                }
+                inverters.push(inverter);
+            }
+            None => {
+                log!(format!("No IPv4 address for inverter {}", host));
+            }
+        }
+    }
+    inverters
 }
 
 #[tokio::main]
@@ -143,62 +697,212 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let mut gauges: HashMap<&'static str, GaugeVec> = HashMap::new();
 
     let gauge_opts = Opts::new(BAT_VOLTAGE, "Battery voltage");
-    let gauge = GaugeVec::new(gauge_opts, &["line"]).unwrap();
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id", "line"]).unwrap();
     register(Box::new(gauge.borrow().clone())).unwrap();
     gauges.insert(BAT_VOLTAGE, gauge);
 
     let gauge_opts = Opts::new(BAT_CURRENT, "Battery current");
-    let gauge = GaugeVec::new(gauge_opts, &["line"]).unwrap();
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id", "line"]).unwrap();
     register(Box::new(gauge.borrow().clone())).unwrap();
     gauges.insert(BAT_CURRENT, gauge);
 
     let gauge_opts = Opts::new(BAT_CHARGE, "Battery charge");
-    let gauge = GaugeVec::new(gauge_opts, &["line"]).unwrap();
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id", "line"]).unwrap();
     register(Box::new(gauge.borrow().clone())).unwrap();
     gauges.insert(BAT_CHARGE, gauge);
 
     let gauge_opts = Opts::new(BAT_TEMPERATURE, "Battery temperature");
-    let gauge = GaugeVec::new(gauge_opts, &["line"]).unwrap();
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id", "line"]).unwrap();
     register(Box::new(gauge.borrow().clone())).unwrap();
     gauges.insert(BAT_TEMPERATURE, gauge);
 
+    let gauge_opts = Opts::new(BAT_STATE_OF_HEALTH, "Battery state of health");
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id", "line"]).unwrap();
+    register(Box::new(gauge.borrow().clone())).unwrap();
+    gauges.insert(BAT_STATE_OF_HEALTH, gauge);
+
+    let gauge_opts = Opts::new(BAT_RATED_CAPACITY, "Battery rated capacity");
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id", "line"]).unwrap();
+    register(Box::new(gauge.borrow().clone())).unwrap();
+    gauges.insert(BAT_RATED_CAPACITY, gauge);
+
+    let gauge_opts = Opts::new(BAT_POWER, "Battery charging and discharging power");
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id", "line", "direction"]).unwrap();
+    register(Box::new(gauge.borrow().clone())).unwrap();
+    gauges.insert(BAT_POWER, gauge);
+
+    let gauge_opts = Opts::new(BAT_OPERATING_STATE, "Battery operating state, 1 for the current state");
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id", "line", "state"]).unwrap();
+    register(Box::new(gauge.borrow().clone())).unwrap();
+    gauges.insert(BAT_OPERATING_STATE, gauge);
+
     let gauge_opts = Opts::new(DC_VOLTAGE, "Spot DC voltage");
-    let gauge = GaugeVec::new(gauge_opts, &["line"]).unwrap();
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id", "line"]).unwrap();
     register(Box::new(gauge.borrow().clone())).unwrap();
     gauges.insert(DC_VOLTAGE, gauge);
 
     let gauge_opts = Opts::new(DC_CURRENT, "Spot DC current");
-    let gauge = GaugeVec::new(gauge_opts, &["line"]).unwrap();
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id", "line"]).unwrap();
     register(Box::new(gauge.borrow().clone())).unwrap();
     gauges.insert(DC_CURRENT, gauge);
 
+    let gauge_opts = Opts::new(DC_POWER, "Spot DC power");
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id", "line"]).unwrap();
+    register(Box::new(gauge.borrow().clone())).unwrap();
+    gauges.insert(DC_POWER, gauge);
+
     let gauge_opts = Opts::new(PRODUCTION_TOTAL, "Total Production");
-    let gauge = GaugeVec::new(gauge_opts, &["inverter"]).unwrap();
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id"]).unwrap();
     register(Box::new(gauge.borrow().clone())).unwrap();
     gauges.insert(PRODUCTION_TOTAL, gauge);
 
     let gauge_opts = Opts::new(AC_VOLTAGE, "Spot AC voltage");
-    let gauge = GaugeVec::new(gauge_opts, &["line"]).unwrap();
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id", "line"]).unwrap();
     register(Box::new(gauge.borrow().clone())).unwrap();
     gauges.insert(AC_VOLTAGE, gauge);
 
     let gauge_opts = Opts::new(AC_CURRENT, "Spot AC current");
-    let gauge = GaugeVec::new(gauge_opts, &["line"]).unwrap();
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id", "line"]).unwrap();
     register(Box::new(gauge.borrow().clone())).unwrap();
     gauges.insert(AC_CURRENT, gauge);
 
+    let gauge_opts = Opts::new(AC_REACTIVE_POWER, "Spot AC reactive power");
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id", "line"]).unwrap();
+    register(Box::new(gauge.borrow().clone())).unwrap();
+    gauges.insert(AC_REACTIVE_POWER, gauge);
+
+    let gauge_opts = Opts::new(AC_APPARENT_POWER, "Spot AC apparent power");
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id", "line"]).unwrap();
+    register(Box::new(gauge.borrow().clone())).unwrap();
+    gauges.insert(AC_APPARENT_POWER, gauge);
+
+    let gauge_opts = Opts::new(GRID_FREQUENCY, "Grid frequency");
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id"]).unwrap();
+    register(Box::new(gauge.borrow().clone())).unwrap();
+    gauges.insert(GRID_FREQUENCY, gauge);
+
+    let gauge_opts = Opts::new(GRID_POWER_FACTOR, "Displacement power factor");
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id"]).unwrap();
+    register(Box::new(gauge.borrow().clone())).unwrap();
+    gauges.insert(GRID_POWER_FACTOR, gauge);
+
+    let gauge_opts = Opts::new(AC_POWER, "Spot AC power");
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id", "line"]).unwrap();
+    register(Box::new(gauge.borrow().clone())).unwrap();
+    gauges.insert(AC_POWER, gauge);
+
+    let gauge_opts = Opts::new(AC_TOTAL_POWER, "Spot AC power total");
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id"]).unwrap();
+    register(Box::new(gauge.borrow().clone())).unwrap();
+    gauges.insert(AC_TOTAL_POWER, gauge);
+
     let gauge_opts = Opts::new(PRODUCTION_DAILY, "Daily Production");
-    let gauge = GaugeVec::new(gauge_opts, &["inverter"]).unwrap();
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id"]).unwrap();
     register(Box::new(gauge.borrow().clone())).unwrap();
     gauges.insert(PRODUCTION_DAILY, gauge);
 
+    let gauge_opts = Opts::new(BAT_DAILY_CHARGED_ENERGY, "Battery charged energy today");
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id", "line"]).unwrap();
+    register(Box::new(gauge.borrow().clone())).unwrap();
+    gauges.insert(BAT_DAILY_CHARGED_ENERGY, gauge);
+
+    let gauge_opts = Opts::new(BAT_DAILY_DISCHARGED_ENERGY, "Battery discharged energy today");
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id", "line"]).unwrap();
+    register(Box::new(gauge.borrow().clone())).unwrap();
+    gauges.insert(BAT_DAILY_DISCHARGED_ENERGY, gauge);
+
+    let gauge_opts = Opts::new(GRID_EXPORT_POWER, "Power fed into the grid");
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id"]).unwrap();
+    register(Box::new(gauge.borrow().clone())).unwrap();
+    gauges.insert(GRID_EXPORT_POWER, gauge);
+
+    let gauge_opts = Opts::new(GRID_IMPORT_POWER, "Power drawn from the grid");
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id"]).unwrap();
+    register(Box::new(gauge.borrow().clone())).unwrap();
+    gauges.insert(GRID_IMPORT_POWER, gauge);
+
+    let gauge_opts = Opts::new(DEVICE_INFO, "Device information, always 1");
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id", "address", "model", "name", "firmware"]).unwrap();
+    register(Box::new(gauge.borrow().clone())).unwrap();
+    gauges.insert(DEVICE_INFO, gauge);
+
+    let gauge_opts = Opts::new(DEVICE_STATUS, "Device status, 1 for the current status");
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id", "status"]).unwrap();
+    register(Box::new(gauge.borrow().clone())).unwrap();
+    gauges.insert(DEVICE_STATUS, gauge);
+
+    let gauge_opts = Opts::new(GRID_RELAY_STATUS, "Grid relay status, 1 for the current status");
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id", "status"]).unwrap();
+    register(Box::new(gauge.borrow().clone())).unwrap();
+    gauges.insert(GRID_RELAY_STATUS, gauge);
+
+    let gauge_opts = Opts::new(INVERTER_TEMPERATURE, "Internal inverter temperature");
+    let gauge = GaugeVec::new(gauge_opts, &["serial", "susy_id"]).unwrap();
+    register(Box::new(gauge.borrow().clone())).unwrap();
+    gauges.insert(INVERTER_TEMPERATURE, gauge);
+
+    let mut counters: HashMap<&'static str, CounterVec> = HashMap::new();
+
+    let counter_opts = Opts::new(OPERATING_TIME, "Operating time");
+    let counter = CounterVec::new(counter_opts, &["serial", "susy_id"]).unwrap();
+    register(Box::new(counter.borrow().clone())).unwrap();
+    counters.insert(OPERATING_TIME, counter);
+
+    let counter_opts = Opts::new(FEED_IN_TIME, "Feed-in time");
+    let counter = CounterVec::new(counter_opts, &["serial", "susy_id"]).unwrap();
+    register(Box::new(counter.borrow().clone())).unwrap();
+    counters.insert(FEED_IN_TIME, counter);
+
+    let counter_opts = Opts::new(BAT_CHARGE_CYCLES, "Battery charge cycles");
+    let counter = CounterVec::new(counter_opts, &["serial", "susy_id", "line"]).unwrap();
+    register(Box::new(counter.borrow().clone())).unwrap();
+    counters.insert(BAT_CHARGE_CYCLES, counter);
+
+    let counter_opts = Opts::new(BAT_CHARGED_AH, "Battery charged amp hours");
+    let counter = CounterVec::new(counter_opts, &["serial", "susy_id", "line"]).unwrap();
+    register(Box::new(counter.borrow().clone())).unwrap();
+    counters.insert(BAT_CHARGED_AH, counter);
+
+    let counter_opts = Opts::new(BAT_DISCHARGED_AH, "Battery discharged amp hours");
+    let counter = CounterVec::new(counter_opts, &["serial", "susy_id", "line"]).unwrap();
+    register(Box::new(counter.borrow().clone())).unwrap();
+    counters.insert(BAT_DISCHARGED_AH, counter);
+
+    let counter_opts = Opts::new(BAT_CHARGED_ENERGY, "Battery charged energy");
+    let counter = CounterVec::new(counter_opts, &["serial", "susy_id", "line"]).unwrap();
+    register(Box::new(counter.borrow().clone())).unwrap();
+    counters.insert(BAT_CHARGED_ENERGY, counter);
+
+    let counter_opts = Opts::new(BAT_DISCHARGED_ENERGY, "Battery discharged energy");
+    let counter = CounterVec::new(counter_opts, &["serial", "susy_id", "line"]).unwrap();
+    register(Box::new(counter.borrow().clone())).unwrap();
+    counters.insert(BAT_DISCHARGED_ENERGY, counter);
+
+    let counter_opts = Opts::new(GRID_EXPORT_ENERGY, "Energy fed into the grid");
+    let counter = CounterVec::new(counter_opts, &["serial", "susy_id"]).unwrap();
+    register(Box::new(counter.borrow().clone())).unwrap();
+    counters.insert(GRID_EXPORT_ENERGY, counter);
+
+    let counter_opts = Opts::new(GRID_IMPORT_ENERGY, "Energy drawn from the grid");
+    let counter = CounterVec::new(counter_opts, &["serial", "susy_id"]).unwrap();
+    register(Box::new(counter.borrow().clone())).unwrap();
+    counters.insert(GRID_IMPORT_ENERGY, counter);
+
     let addr = SocketAddr::from(([0, 0, 0, 0], 9756));
 
     thread::spawn(move || {
         let mut counter = 0;
-        let mut socket = initialize_socket(false);
+        let mut socket = match initialize_socket(false, Ipv4Addr::UNSPECIFIED) {
+            Ok(socket) => socket,
+            Err(error) => {
+                log!(format!("Unable to open socket. {}", error));
+                exit(1);
+            }
+        };
 
         let mut logged_in_inverters: Vec<Inverter> = Vec::new();
+        let mut not_available = NotAvailable::Nan;
+        let mut device_info: HashMap<String, Vec<String>> = HashMap::new();
 
         loop {
             thread::sleep(Duration::from_secs(10));
@@ -216,27 +920,80 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                     Ok(config) => config,
                 };
 
-                let inverters = match find_inverters() {
-                    Ok(found_inverters) => found_inverters,
+                not_available = match settings.get_string("not_available").as_deref() {
+                    Ok("remove") => NotAvailable::Remove,
+                    Ok("nan") | Err(_) => NotAvailable::Nan,
+                    Ok(other) => {
+                        log!(format!("Unknown value for not_available: {}, using nan", other));
+                        NotAvailable::Nan
+                    }
+                };
+
+                let mut inverters: Vec<Inverter> = Vec::new();
+                let interfaces = discovery_interfaces(&settings);
+                match find_inverters(&interfaces) {
+                    Ok(devices) => {
+                        for device in devices {
+                            if device.is_inverter() {
+                                let mut inverter = Inverter::new(device.address);
+                                inverter.device_susy_id = device.susy_id;
+                                inverter.device_serial = device.serial;
+                                inverters.push(inverter);
+                            } else {
+                                log!(format!("Skipping {} {}", device.device_class, device.address));
+                            }
+                        }
+                    }
                     Err(err) => {
                         log!(format!("Error while finding inverters: {}", err));
-                        Vec::new()
                     }
-                };
+                }
 
-                socket = initialize_socket(false);
+                // Configured inverters take precedence over discovered ones at the same address.
+                let configured_inverters = static_inverters(&settings);
+                inverters.retain(|i| !configured_inverters.iter().any(|c| c.address == i.address));
+                inverters.extend(configured_inverters);
+
+                // With a single interface the inverter traffic uses it as well, otherwise routing decides.
+                let local_address = match interfaces.as_slice() {
+                    [interface] => *interface,
+                    _ => Ipv4Addr::UNSPECIFIED,
+                };
+                socket = match initialize_socket(false, local_address) {
+                    Ok(socket) => socket,
+                    Err(error) => {
+                        // Try again with the next discovery.
+                        log!(format!("Unable to open socket on {}. {}", local_address, error));
+                        continue;
+                    }
+                };
 
                 for mut i in inverters.iter().cloned() {
-                    let pass_key = format!("{}{}", &i.address.ip().to_string(), ".password");
-                    let password = settings
-                        .get_string(pass_key.as_str())
+                    let password = inverter_setting(&settings, &i, "password")
                         .unwrap_or("0000".to_string());
-                    match i.login(&socket, password.as_str()) {
+                    let user_group = match inverter_setting(&settings, &i, "user_group").as_deref() {
+                        Some("installer") => UserGroup::Installer,
+                        Some("user") | None => UserGroup::User,
+                        Some(other) => {
+                            log!(format!("Unknown user_group for inverter {}: {}, using user", i.address, other));
+                            UserGroup::User
+                        }
+                    };
+                    match i.login(&socket, user_group, password.as_str()) {
                         Ok(_result) => {
-                            logged_in_inverters.push(i);
+                            // Most devices only report their nameplate class after login.
+                            if let Ok(device_info) = i.get_device_info(&socket)
+                                && !DeviceClass::new(i.device_susy_id, device_info.device_class).is_inverter()
+                            {
+                                log!(format!("Skipping {} {}",
+                                    DeviceClass::new(i.device_susy_id, device_info.device_class), i.address));
+                                i.logoff(&socket);
+                            } else {
+                                logged_in_inverters.push(i);
+                            }
                         }
                         Err(inverter_error) => {
-                            log!(format!("Inverter {} error: {}", i.address, inverter_error.message));
+                            log!(format!("Inverter {} error: {}", i.address, inverter_error));
                         }
                     }
                 }
@@ -262,177 +1019,22 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 
             log!("Getting data from inverters: ");
             for i in &mut logged_in_inverters {
-                log!(format!("Getting data from inverter {}.", &i.address.ip().to_string()));
-                match i.get_battery_info(&socket) {
-                    Ok(data) => {
-                        let _lock = LOCK.lock().unwrap();
-                        gauges
-                            .get(BAT_TEMPERATURE)
-                            .unwrap()
-                            .with_label_values(&["A"])
-                            .set(data.temperature[0] as f64 / 10_f64);
-                        gauges
-                            .get(BAT_TEMPERATURE)
-                            .unwrap()
-                            .with_label_values(&["B"])
-                            .set(data.temperature[1] as f64 / 10_f64);
-                        gauges
-                            .get(BAT_TEMPERATURE)
-                            .unwrap()
-                            .with_label_values(&["C"])
-                            .set(data.temperature[2] as f64 / 10_f64);
-                        gauges
-                            .get(BAT_VOLTAGE)
-                            .unwrap()
-                            .with_label_values(&["A"])
-                            .set(data.voltage[0] as f64 * 10_f64);
-                        gauges
-                            .get(BAT_VOLTAGE)
-                            .unwrap()
-                            .with_label_values(&["B"])
-                            .set(data.voltage[1] as f64 * 10_f64);
-                        gauges
-                            .get(BAT_VOLTAGE)
-                            .unwrap()
-                            .with_label_values(&["C"])
-                            .set(data.voltage[2] as f64 * 10_f64);
-                        gauges
-                            .get(BAT_CURRENT)
-                            .unwrap()
-                            .with_label_values(&["A"])
-                            .set(data.current[0] as f64);
-                        gauges
-                            .get(BAT_CURRENT)
-                            .unwrap()
-                            .with_label_values(&["B"])
-                            .set(data.current[1] as f64);
-                        gauges
-                            .get(BAT_CURRENT)
-                            .unwrap()
-                            .with_label_values(&["C"])
-                            .set(data.current[2] as f64);
-                    }
-                    Err(inverter_error) => {
-                        if inverter_error.message.ne("Unsupported") {
-                            log!(format!("[{}] Unable to get battery info from inverter. {}",
-                                &i.address.ip().to_string(), inverter_error.message));
-                        }
-                    }
-                }
-                match i.get_dc_voltage(socket.borrow()) {
-                    Ok(data) => {
-                        gauges
-                            .get(DC_CURRENT)
-                            .unwrap()
-                            .with_label_values(&["1"])
-                            .set(data.current[0] as f64);
-                        gauges
-                            .get(DC_CURRENT)
-                            .unwrap()
-                            .with_label_values(&["2"])
-                            .set(data.current[1] as f64);
-                        gauges
-                            .get(DC_VOLTAGE)
-                            .unwrap()
-                            .with_label_values(&["1"])
-                            .set(data.voltage[0] as f64 * 10_f64);
-                        gauges
-                            .get(DC_VOLTAGE)
-                            .unwrap()
-                            .with_label_values(&["2"])
-                            .set(data.voltage[1] as f64 * 10_f64);
-                    }
-                    Err(inverter_error) => {
-                        if inverter_error.message.ne("Unsupported") {
-                            log!(format!("[{}] Unable to get DC voltage from inverter. {}",
-                                &i.address.ip().to_string(), inverter_error.message));
-                        }
-                    }
-                }
-                match i.get_ac_voltage(socket.borrow()) {
-                    Ok(data) => {
-                        gauges
-                            .get(AC_CURRENT)
-                            .unwrap()
-                            .with_label_values(&["1"])
-                            .set(data.current[0] as f64);
-                        gauges
-                            .get(AC_CURRENT)
-                            .unwrap()
-                            .with_label_values(&["2"])
-                            .set(data.current[1] as f64);
-                        gauges
-                            .get(AC_CURRENT)
-                            .unwrap()
-                            .with_label_values(&["3"])
-                            .set(data.current[2] as f64);
-                        gauges
-                            .get(AC_VOLTAGE)
-                            .unwrap()
-                            .with_label_values(&["1"])
-                            .set(data.voltage[0] as f64 * 10_f64);
-                        gauges
-                            .get(AC_VOLTAGE)
-                            .unwrap()
-                            .with_label_values(&["2"])
-                            .set(data.voltage[1] as f64 * 10_f64);
-                        gauges
-                            .get(AC_VOLTAGE)
-                            .unwrap()
-                            .with_label_values(&["3"])
-                            .set(data.voltage[2] as f64 * 10_f64);
-                    }
-                    Err(inverter_error) => {
-                        if inverter_error.message.ne("Unsupported") {
-                            println!("Inverter error: {}", inverter_error.message);
-                        }
-                    }
-                }
-                match i.get_battery_charge_status(socket.borrow()) {
-                    Ok(data) => {
-                        let _lock = LOCK.lock().unwrap();
-                        gauges
-                            .get(BAT_CHARGE)
-                            .unwrap()
-                            .with_label_values(&["A"])
-                            .set(data[0] as f64);
-                        gauges
-                            .get(BAT_CHARGE)
-                            .unwrap()
-                            .with_label_values(&["B"])
-                            .set(data[1] as f64);
-                        gauges
-                            .get(BAT_CHARGE)
-                            .unwrap()
-                            .with_label_values(&["C"])
-                            .set(data[2] as f64);
-                    }
-                    Err(inverter_error) => {
-                        if inverter_error.message.ne("Unsupported") {
-                            log!(format!("Inverter error: {}", inverter_error.message));
-                        }
-                    }
+                if i.needs_login() && !relogin(i, &socket) {
+                    continue;
                 }
-                match i.get_energy_production(socket.borrow()) {
-                    Ok(data) => {
-                        let _lock = LOCK.lock().unwrap();
-                        gauges
-                            .get(PRODUCTION_DAILY)
-                            .unwrap()
-                            .with_label_values(&[&i.address.ip().to_string()])
-                            .set(data.daily_wh as f64);
-                        gauges
-                            .get(PRODUCTION_TOTAL)
-                            .unwrap()
-                            .with_label_values(&[&i.address.ip().to_string()])
-                            .set(data.total_wh as f64);
-                    }
-                    Err(inverter_error) => {
-                        if inverter_error.message.ne("Unsupported") {
-                            log!(format!("[{}] Unable to get energy production from inverter. {}",
-                                &i.address.ip().to_string(), inverter_error.message));
-                        }
-                    }
+                log!(format!("Getting data from inverter {}.", &i.address.ip().to_string()));
+                let mut context = PollContext {
+                    socket: &socket,
+                    gauges: &gauges,
+                    counters: &counters,
+                    not_available,
+                    serial: i.device_serial.to_string(),
+                    susy_id: i.device_susy_id.to_string(),
+                    device_info: &mut device_info,
+                };
+                poll_inverter(i, &mut context);
+                if i.needs_login() {
+                    relogin(i, &socket);
                 }
             }
             log!("Finished getting data from all inverters.");
@@ -1,20 +1,25 @@
 extern crate socket2;
 
 use self::socket2::{Domain, Protocol, SockAddr, Socket, Type};
+#[cfg(unix)]
+use std::ffi::CStr;
+use std::io;
 use std::net::{Ipv4Addr, SocketAddr};
 use tokio::time::Duration;
 
 use crate::log;
 
-pub fn initialize_socket(multicast: bool) -> Socket {
-    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP)).unwrap();
-    socket.set_reuse_address(true).unwrap();
-    socket
-        .bind(&SockAddr::from(SocketAddr::new(
-            Ipv4Addr::new(0, 0, 0, 0).into(),
-            9522,
-        )))
-        .unwrap();
+/// Creates the socket for inverter traffic from `interface`, `0.0.0.0` leaves the choice to the
+/// kernel. Multicast sockets stay bound to all addresses to receive the group traffic.
+pub fn initialize_socket(multicast: bool, interface: Ipv4Addr) -> io::Result<Socket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    let bind_address = if multicast {
+        Ipv4Addr::UNSPECIFIED
+    } else {
+        interface
+    };
+    socket.bind(&SockAddr::from(SocketAddr::new(bind_address.into(), 9522)))?;
     match socket.set_read_timeout(Some(Duration::from_secs(1))) {
         Ok(()) => {}
         Err(error) => {
@@ -23,12 +28,44 @@ pub fn initialize_socket(multicast: bool) -> Socket {
     }
 
     if multicast {
-        assert!(socket
-            .join_multicast_v4(
-                &Ipv4Addr::new(239, 12, 255, 254),
-                &Ipv4Addr::new(0, 0, 0, 0)
-            )
-            .is_ok());
+        socket.join_multicast_v4(&Ipv4Addr::new(239, 12, 255, 254), &interface)?;
+        if !interface.is_unspecified() {
+            socket.set_multicast_if_v4(&interface)?;
+        }
+    }
+    Ok(socket)
+}
+
+/// Looks up the first IPv4 address of the network interface `name`, e.g. `eth1`.
+#[cfg(unix)]
+pub fn interface_address(name: &str) -> Option<Ipv4Addr> {
+    let mut addresses: *mut libc::ifaddrs = std::ptr::null_mut();
+    if unsafe { libc::getifaddrs(&mut addresses) } != 0 {
+        return None;
+    }
+
+    let mut result = None;
+    let mut current = addresses;
+    while !current.is_null() {
+        let entry = unsafe { &*current };
+        let address = entry.ifa_addr;
+        if !address.is_null()
+            && i32::from(unsafe { (*address).sa_family }) == libc::AF_INET
+            && unsafe { CStr::from_ptr(entry.ifa_name) }.to_bytes() == name.as_bytes()
+        {
+            let address = unsafe { &*(address as *const libc::sockaddr_in) };
+            result = Some(Ipv4Addr::from(u32::from_be(address.sin_addr.s_addr)));
+            break;
+        }
+        current = entry.ifa_next;
     }
-    socket
+
+    unsafe { libc::freeifaddrs(addresses) };
+    result
+}
+
+/// Interface names are only supported on Unix, elsewhere interfaces are given by address.
+#[cfg(not(unix))]
+pub fn interface_address(_name: &str) -> Option<Ipv4Addr> {
+    None
 }